@@ -4,7 +4,7 @@ import { FC, useState } from 'react'
 import { useWallet } from '@solana/wallet-adapter-react'
 import { PublicKey, LAMPORTS_PER_SOL, SystemProgram } from '@solana/web3.js'
 import * as anchor from '@coral-xyz/anchor'
-import { useBlinkPay, useBlinkPayProgramId } from '@/lib/useBlinkPay'
+import { useBlinkPay, nextIndexedPda } from '@/lib/useBlinkPay'
 import { Button } from './ui/Button'
 import { X, Send, Loader2 } from 'lucide-react'
 
@@ -16,7 +16,6 @@ interface PaymentModalProps {
 export const PaymentModal: FC<PaymentModalProps> = ({ isOpen, onClose }) => {
   const { publicKey } = useWallet()
   const program = useBlinkPay()
-  const programId = useBlinkPayProgramId()
 
   const [recipient, setRecipient] = useState('')
   const [amount, setAmount] = useState('')
@@ -38,32 +37,33 @@ export const PaymentModal: FC<PaymentModalProps> = ({ isOpen, onClose }) => {
         throw new Error('Amount must be greater than 0')
       }
 
-      const currentTime = Math.floor(Date.now() / 1000)
+      const { address: paymentRequest, userProfile, preInstructions } = await nextIndexedPda(
+        program,
+        'payment_request',
+        publicKey,
+        'paymentRequestCount'
+      )
 
       // Create payment request
       await program.methods
         .createPaymentRequest(
-          amountLamports,
-          programId, // SOL token mint
+          new anchor.BN(amountLamports),
+          SystemProgram.programId, // SOL token mint
           new PublicKey(recipient),
           memo || 'Payment via BlikPay',
-          currentTime
+          null, // no expiry
+          false, // paid in full
+          null, // any payer
+          null // no escrow
         )
         .accounts({
           authority: publicKey,
-          paymentRequest: PublicKey.findProgramAddressSync(
-            [
-              Buffer.from('payment_request'),
-              publicKey.toBuffer(),
-              new PublicKey(recipient).toBuffer(),
-              new anchor.BN(amountLamports).toArrayLike(Buffer, 'le', 8),
-              new anchor.BN(currentTime).toArrayLike(Buffer, 'le', 8),
-            ],
-            programId
-          )[0],
+          userProfile,
+          paymentRequest,
           systemProgram: SystemProgram.programId,
           clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
         })
+        .preInstructions(preInstructions)
         .rpc()
 
       onClose()
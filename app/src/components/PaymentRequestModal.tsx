@@ -2,9 +2,9 @@
 
 import { FC, useState } from 'react'
 import { useWallet } from '@solana/wallet-adapter-react'
-import { PublicKey, LAMPORTS_PER_SOL, SystemProgram } from '@solana/web3.js'
+import { LAMPORTS_PER_SOL, SystemProgram } from '@solana/web3.js'
 import * as anchor from '@coral-xyz/anchor'
-import { useBlinkPay, useBlinkPayProgramId } from '@/lib/useBlinkPay'
+import { useBlinkPay, nextIndexedPda } from '@/lib/useBlinkPay'
 import { Button } from './ui/Button'
 import { X, Plus, Loader2, Copy, Check } from 'lucide-react'
 
@@ -16,7 +16,6 @@ interface PaymentRequestModalProps {
 export const PaymentRequestModal: FC<PaymentRequestModalProps> = ({ isOpen, onClose }) => {
   const { publicKey } = useWallet()
   const program = useBlinkPay()
-  const programId = useBlinkPayProgramId()
 
   const [amount, setAmount] = useState('')
   const [memo, setMemo] = useState('')
@@ -39,45 +38,37 @@ export const PaymentRequestModal: FC<PaymentRequestModalProps> = ({ isOpen, onCl
         throw new Error('Amount must be greater than 0')
       }
 
-      const currentTime = Math.floor(Date.now() / 1000)
+      const { address: paymentRequest, userProfile, preInstructions } = await nextIndexedPda(
+        program,
+        'payment_request',
+        publicKey,
+        'paymentRequestCount'
+      )
 
       // Create payment request
       await program.methods
         .createPaymentRequest(
-          amountLamports,
-          programId,
+          new anchor.BN(amountLamports),
+          SystemProgram.programId, // SOL token mint
           publicKey, // recipient is the current user
           memo || 'Payment request via BlikPay',
-          currentTime
+          null, // no expiry
+          false, // paid in full
+          null, // any payer
+          null // no escrow
         )
         .accounts({
           authority: publicKey,
-          paymentRequest: PublicKey.findProgramAddressSync(
-            [
-              Buffer.from('payment_request'),
-              publicKey.toBuffer(),
-              publicKey.toBuffer(), // recipient is also authority
-              new anchor.BN(amountLamports).toArrayLike(Buffer, 'le', 8),
-              new anchor.BN(currentTime).toArrayLike(Buffer, 'le', 8),
-            ],
-            programId
-          )[0],
+          userProfile,
+          paymentRequest,
           systemProgram: SystemProgram.programId,
           clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
         })
+        .preInstructions(preInstructions)
         .rpc()
 
       // Generate shareable link
-      const requestId = PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('payment_request'),
-          publicKey.toBuffer(),
-          publicKey.toBuffer(),
-          new anchor.BN(amountLamports).toArrayLike(Buffer, 'le', 8),
-          new anchor.BN(currentTime).toArrayLike(Buffer, 'le', 8),
-        ],
-        programId
-      )[0].toString()
+      const requestId = paymentRequest.toString()
 
       setRequestLink(`${window.location.origin}/pay/${requestId}`)
 
@@ -4,7 +4,7 @@ import { FC, useState } from 'react'
 import { useWallet } from '@solana/wallet-adapter-react'
 import { PublicKey, LAMPORTS_PER_SOL, SystemProgram } from '@solana/web3.js'
 import * as anchor from '@coral-xyz/anchor'
-import { useBlinkPay, useBlinkPayProgramId } from '@/lib/useBlinkPay'
+import { useBlinkPay, nextIndexedPda } from '@/lib/useBlinkPay'
 import { Button } from './ui/Button'
 import { X, Clock, Loader2 } from 'lucide-react'
 
@@ -16,7 +16,6 @@ interface ScheduledPaymentModalProps {
 export const ScheduledPaymentModal: FC<ScheduledPaymentModalProps> = ({ isOpen, onClose }) => {
   const { publicKey } = useWallet()
   const program = useBlinkPay()
-  const programId = useBlinkPayProgramId()
 
   const [recipient, setRecipient] = useState('')
   const [amount, setAmount] = useState('')
@@ -45,36 +44,46 @@ export const ScheduledPaymentModal: FC<ScheduledPaymentModalProps> = ({ isOpen,
         throw new Error('Execution time must be in the future')
       }
 
-      const currentTime = Math.floor(Date.now() / 1000)
+      const { address: scheduledCharge, userProfile, preInstructions } = await nextIndexedPda(
+        program,
+        'scheduled_charge',
+        publicKey,
+        'scheduledChargeCount'
+      )
 
       await program.methods
-        .createScheduledCharge(
-          amountLamports,
-          programId,
-          recipientPubkey,
-          executeTimestamp,
-          0, // OneTime
-          null,
-          null,
-          memo || 'Scheduled payment via BlikPay',
-          currentTime
-        )
+        .createScheduledCharge({
+          amount: new anchor.BN(amountLamports),
+          tokenMint: SystemProgram.programId, // SOL token mint
+          recipient: recipientPubkey,
+          executeAt: new anchor.BN(executeTimestamp),
+          chargeType: 0, // OneTime
+          recurrence: null,
+          maxExecutions: null,
+          memo: memo || 'Scheduled payment via BlikPay',
+          vaultDeposit: null,
+          keeperTip: null,
+          catchUpPolicy: null,
+          endAt: null,
+          trialSeconds: null,
+          introOffer: null,
+          gracePeriod: null,
+          unitPrice: null,
+          meterAuthority: null,
+        })
         .accounts({
           authority: publicKey,
-          scheduledCharge: PublicKey.findProgramAddressSync(
-            [
-              Buffer.from('scheduled_charge'),
-              publicKey.toBuffer(),
-              recipientPubkey.toBuffer(),
-              new anchor.BN(amountLamports).toArrayLike(Buffer, 'le', 8),
-              new anchor.BN(executeTimestamp).toArrayLike(Buffer, 'le', 8),
-              new Uint8Array([0]),
-            ],
-            programId
-          )[0],
+          userProfile,
+          scheduledCharge,
+          authorityTokenAccount: null,
+          chargeDelegate: null,
+          chargeVault: null,
+          vaultTokenAccount: null,
+          tokenProgram: null,
           systemProgram: SystemProgram.programId,
           clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
         })
+        .preInstructions(preInstructions)
         .rpc()
 
       onClose()
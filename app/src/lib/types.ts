@@ -10,6 +10,11 @@ export type Blinkpay = {
           "isMut": true,
           "isSigner": true
         },
+        {
+          "name": "userProfile",
+          "isMut": true,
+          "isSigner": false
+        },
         {
           "name": "paymentRequest",
           "isMut": true,
@@ -44,8 +49,26 @@ export type Blinkpay = {
           "type": "string"
         },
         {
-          "name": "currentTime",
-          "type": "i64"
+          "name": "expiresAt",
+          "type": {
+            "option": "i64"
+          }
+        },
+        {
+          "name": "allowPartial",
+          "type": "bool"
+        },
+        {
+          "name": "designatedPayer",
+          "type": {
+            "option": "publicKey"
+          }
+        },
+        {
+          "name": "escrowTimeoutSeconds",
+          "type": {
+            "option": "u64"
+          }
         }
       ]
     }
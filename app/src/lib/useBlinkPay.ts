@@ -2,9 +2,9 @@
 
 import { useMemo } from 'react'
 import { useAnchorWallet, useConnection } from '@solana/wallet-adapter-react'
-import { Program, Idl } from '@coral-xyz/anchor'
+import { BN, Program, Idl } from '@coral-xyz/anchor'
 import idl from './idl.json'
-import { PublicKey } from '@solana/web3.js'
+import { PublicKey, SystemProgram, TransactionInstruction } from '@solana/web3.js'
 
 // Program ID
 const PROGRAM_ID = new PublicKey('GCdgRD3ss44Qyr9QpS3nj1u6UwbXnua8jU1EXazwyyPV')
@@ -26,4 +26,45 @@ export function useBlinkPay() {
 
 export function useBlinkPayProgramId() {
   return PROGRAM_ID
-}
\ No newline at end of file
+}
+
+export function userProfilePda(owner: PublicKey) {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('user_profile'), owner.toBuffer()],
+    PROGRAM_ID
+  )[0]
+}
+
+// Accounts are seeded by the owner's profile counter, so the next address is read from the profile.
+// A wallet without a profile yet gets the instruction creating it, to run before the create call.
+export async function nextIndexedPda(
+  program: Program,
+  seed: 'payment_request' | 'split_request' | 'scheduled_charge' | 'plan',
+  owner: PublicKey,
+  counter: 'paymentRequestCount' | 'splitRequestCount' | 'scheduledChargeCount' | 'planCount'
+) {
+  const userProfile = userProfilePda(owner)
+  const profile: any = await program.account.userProfile.fetchNullable(userProfile)
+
+  const preInstructions: TransactionInstruction[] = []
+  if (!profile) {
+    preInstructions.push(
+      await program.methods
+        .initializeUserProfile()
+        .accounts({
+          authority: owner,
+          userProfile,
+          systemProgram: SystemProgram.programId,
+        })
+        .instruction()
+    )
+  }
+
+  const index = profile ? profile[counter] : new BN(0)
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from(seed), owner.toBuffer(), index.toArrayLike(Buffer, 'le', 8)],
+    PROGRAM_ID
+  )
+
+  return { address, userProfile, preInstructions }
+}
@@ -78,4 +78,128 @@ pub enum BlinkPayError {
     /// Associated token account mismatch
     #[msg("Associated token account mismatch")]
     InvalidAssociatedTokenAccount,
+
+    /// Payment request must be paid or cancelled before it can be closed
+    #[msg("Payment request must be paid or cancelled before closing")]
+    PaymentRequestNotSettled,
+
+    /// Payment request has passed its expiry deadline
+    #[msg("Payment request has expired")]
+    PaymentRequestExpired,
+
+    /// Payment request has not expired yet
+    #[msg("Payment request has not expired yet")]
+    PaymentRequestNotExpired,
+
+    /// Payment request does not accept partial payments
+    #[msg("Payment request does not accept partial payments")]
+    PartialPaymentsNotAllowed,
+
+    /// Payment amount exceeds the outstanding balance
+    #[msg("Payment amount exceeds the outstanding balance")]
+    PaymentExceedsOutstanding,
+
+    /// Split request shares are invalid
+    #[msg("Split request must list between 1 and 10 unique payers with non-zero shares")]
+    InvalidSplitShares,
+
+    /// Payer has no share in this split request
+    #[msg("Payer has no share in this split request")]
+    PayerNotInSplit,
+
+    /// Payer's share has already been paid
+    #[msg("Split share has already been paid")]
+    SplitShareAlreadyPaid,
+
+    /// Payer is not allowed to pay this request
+    #[msg("Payer is not the designated payer for this request")]
+    UnauthorizedPayer,
+
+    /// Escrow terms are invalid
+    #[msg("Escrow requires a valid timeout and does not support partial payments")]
+    InvalidEscrowTerms,
+
+    /// Escrow vault account is missing or invalid
+    #[msg("Escrow vault account is missing or invalid")]
+    InvalidEscrowVault,
+
+    /// Payment request is not held in escrow
+    #[msg("Payment request is not held in escrow")]
+    PaymentRequestNotEscrowed,
+
+    /// Escrow timeout has not been reached
+    #[msg("Escrow timeout has not been reached")]
+    EscrowNotExpired,
+
+    /// Payment request cannot be refunded in its current status
+    #[msg("Payment request must be paid before it can be refunded")]
+    PaymentRequestNotRefundable,
+
+    /// Refund amount exceeds what is left to refund
+    #[msg("Refund amount exceeds the amount paid")]
+    RefundExceedsPaid,
+
+    /// Charge delegate account is missing or invalid
+    #[msg("Charge delegate account is missing or invalid")]
+    InvalidChargeDelegate,
+
+    /// Charge vault account is missing or invalid
+    #[msg("Charge vault account is missing or invalid")]
+    InvalidChargeVault,
+
+    /// Scheduled charge is not funded from a vault
+    #[msg("Scheduled charge is not funded from a vault")]
+    ChargeNotVaultFunded,
+
+    /// Recurrence is missing or invalid
+    #[msg("Recurring charges need a recurrence with a valid weekday, day of month or date")]
+    InvalidRecurrence,
+
+    /// Scheduled charge is paused
+    #[msg("Scheduled charge is paused")]
+    ScheduledChargePaused,
+
+    /// Scheduled charge is not paused
+    #[msg("Scheduled charge is not paused")]
+    ScheduledChargeNotPaused,
+
+    /// Scheduled charge has no pending amendment
+    #[msg("Scheduled charge has no pending amendment")]
+    NoPendingAmendment,
+
+    /// Scheduled charge has not reached its end date
+    #[msg("Scheduled charge has not reached its end date")]
+    ScheduledChargeNotExpired,
+
+    /// Trial or introductory offer is invalid
+    #[msg("Trials and introductory offers need a recurring charge, a trial within a year and a reduced non-zero amount for at least one execution")]
+    InvalidIntroductoryTerms,
+
+    /// Past-due charge retried before its backoff elapsed
+    #[msg("Past-due charge cannot be retried yet")]
+    DunningRetryNotReady,
+
+    /// Meter terms are missing or invalid
+    #[msg("Metered charges need a unit price, other charges must not have meter terms")]
+    InvalidMeter,
+
+    /// Scheduled charge is not metered
+    #[msg("Scheduled charge is not metered")]
+    ChargeNotMetered,
+
+    /// Plan is not accepting new subscribers
+    #[msg("Plan is not accepting new subscribers")]
+    PlanInactive,
+
+    /// Plan change is not allowed
+    #[msg("Subscriptions can only change to another plan from the same merchant in the same token and billing schedule")]
+    InvalidPlanChange,
+
+    /// Payment request has no room for another payer
+    #[msg("Payment request already has the maximum number of payers")]
+    TooManyPayers,
+
+    /// Accepted terms differ from the pending amendment
+    #[msg("Accepted terms do not match the pending amendment")]
+    AmendmentMismatch,
 }
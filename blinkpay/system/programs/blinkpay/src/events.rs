@@ -0,0 +1,397 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{CancellationReason, CatchUpPolicy, ChargeFundingMode, IntroductoryOffer, Meter, ProrationSettlement, PaymentRequestStatus, Recurrence, ScheduledChargeStatus, ScheduledChargeType};
+
+/// Emit an event through a self-CPI when the `event-cpi` feature is enabled,
+/// otherwise as a program log
+#[macro_export]
+macro_rules! emit_event {
+    ($ctx:expr, $event:expr) => {{
+        let event = $event;
+        #[cfg(feature = "event-cpi")]
+        {
+            let ctx = &$ctx;
+            anchor_lang::prelude::emit_cpi!(event);
+        }
+        #[cfg(not(feature = "event-cpi"))]
+        {
+            let _ = &$ctx;
+            anchor_lang::prelude::emit!(event);
+        }
+    }};
+}
+
+/// Emitted when a user profile is initialized
+#[event]
+pub struct UserProfileInitialized {
+    pub user_profile: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when a payment request is created
+#[event]
+pub struct PaymentRequestCreated {
+    pub payment_request: Pubkey,
+    pub index: u64,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub expires_at: Option<i64>,
+    pub allow_partial: bool,
+    pub designated_payer: Option<Pubkey>,
+    pub escrow_timeout_seconds: Option<u64>,
+    pub timestamp: i64,
+}
+
+/// Emitted for every payment towards a payment request, including partial and escrowed ones
+#[event]
+pub struct PaymentRequestPaid {
+    pub payment_request: Pubkey,
+    pub payer: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub amount_paid: u64,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when a payment request is cancelled by its authority
+#[event]
+pub struct PaymentRequestCancelled {
+    pub payment_request: Pubkey,
+    pub authority: Pubkey,
+    pub amount_paid: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a payment request account is closed and its rent reclaimed
+#[event]
+pub struct PaymentRequestClosed {
+    pub payment_request: Pubkey,
+    pub authority: Pubkey,
+    pub closed_by: Pubkey,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when escrowed funds are released to the recipient
+#[event]
+pub struct EscrowReleased {
+    pub payment_request: Pubkey,
+    pub released_by: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when escrowed funds are returned to the payer
+#[event]
+pub struct EscrowRefunded {
+    pub payment_request: Pubkey,
+    pub refunded_by: Pubkey,
+    pub payer: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the recipient refunds a paid payment request
+#[event]
+pub struct PaymentRequestRefunded {
+    pub payment_request: Pubkey,
+    pub recipient: Pubkey,
+    pub payer: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub amount_refunded: u64,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when a split request is created
+#[event]
+pub struct SplitRequestCreated {
+    pub split_request: Pubkey,
+    pub index: u64,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub total_amount: u64,
+    pub payers: Vec<Pubkey>,
+    pub timestamp: i64,
+}
+
+/// Emitted when a payer settles their share of a split request
+#[event]
+pub struct SplitSharePaid {
+    pub split_request: Pubkey,
+    pub payer: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub amount_paid: u64,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when a split request is cancelled by its authority
+#[event]
+pub struct SplitRequestCancelled {
+    pub split_request: Pubkey,
+    pub authority: Pubkey,
+    pub amount_paid: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a split request account is closed and its rent reclaimed
+#[event]
+pub struct SplitRequestClosed {
+    pub split_request: Pubkey,
+    pub authority: Pubkey,
+    pub status: PaymentRequestStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when a scheduled charge is created
+#[event]
+pub struct ScheduledChargeCreated {
+    pub scheduled_charge: Pubkey,
+    pub index: u64,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub charge_type: ScheduledChargeType,
+    pub funding_mode: ChargeFundingMode,
+    pub vault_deposit: Option<u64>,
+    pub keeper_tip: u64,
+    pub trial_ends_at: Option<i64>,
+    pub intro_offer: Option<IntroductoryOffer>,
+    pub meter: Option<Meter>,
+    pub execute_at: i64,
+    pub recurrence: Option<Recurrence>,
+    pub catch_up_policy: CatchUpPolicy,
+    pub max_executions: Option<u32>,
+    pub end_at: Option<i64>,
+    pub grace_period: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a scheduled charge is executed
+#[event]
+pub struct ScheduledChargeExecuted {
+    pub scheduled_charge: Pubkey,
+    pub executor: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub keeper_tip: u64,
+    pub usage: u64,
+    pub proration: i64,
+    pub periods_skipped: u64,
+    pub execution_count: u32,
+    pub next_execute_at: Option<i64>,
+    pub status: ScheduledChargeStatus,
+    pub timestamp: i64,
+}
+
+/// Emitted when funds are added to a scheduled charge vault
+#[event]
+pub struct ScheduledChargeToppedUp {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub available_balance: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when uncommitted funds are withdrawn from a scheduled charge vault
+#[event]
+pub struct ScheduledChargeVaultWithdrawn {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub available_balance: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when new terms are proposed for a scheduled charge
+#[event]
+pub struct ScheduledChargeAmendmentProposed {
+    pub scheduled_charge: Pubkey,
+    pub proposed_by: Pubkey,
+    pub amount: u64,
+    pub recurrence: Option<Recurrence>,
+    pub max_executions: Option<u32>,
+    pub recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when the counterparty accepts proposed terms and they take effect
+#[event]
+pub struct ScheduledChargeAmended {
+    pub scheduled_charge: Pubkey,
+    pub proposed_by: Pubkey,
+    pub accepted_by: Pubkey,
+    pub amount: u64,
+    pub recurrence: Option<Recurrence>,
+    pub max_executions: Option<u32>,
+    pub recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when proposed terms are rejected by the counterparty or withdrawn by the proposer
+#[event]
+pub struct ScheduledChargeAmendmentRejected {
+    pub scheduled_charge: Pubkey,
+    pub proposed_by: Pubkey,
+    pub rejected_by: Pubkey,
+    pub unreleased_allowance: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a scheduled charge is paused by its authority
+#[event]
+pub struct ScheduledChargePaused {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub auto_resume_at: Option<i64>,
+    pub timestamp: i64,
+}
+
+/// Emitted when a paused scheduled charge resumes, by its authority or automatically on execution
+#[event]
+pub struct ScheduledChargeResumed {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub resumed_by: Pubkey,
+    pub next_execute_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a scheduled charge is cancelled and closed, by its authority or its recipient
+#[event]
+pub struct ScheduledChargeCancelled {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub reason: CancellationReason,
+    pub execution_count: u32,
+    pub vault_refund: u64,
+    pub unreleased_allowance: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an execution finds too few funds and the charge becomes past due
+#[event]
+pub struct ScheduledChargePastDue {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub amount_due: u64,
+    pub failed_attempts: u32,
+    pub next_retry_at: i64,
+    pub grace_ends_at: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a past-due scheduled charge runs out of grace and stops executing
+#[event]
+pub struct ScheduledChargeSuspended {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub failed_attempts: u32,
+    pub timestamp: i64,
+}
+
+/// Emitted when usage is reported against a metered scheduled charge
+#[event]
+pub struct ScheduledChargeUsageReported {
+    pub scheduled_charge: Pubkey,
+    pub reporter: Pubkey,
+    pub units: u64,
+    pub period_usage: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a scheduled charge reaches its end date and stops executing
+#[event]
+pub struct ScheduledChargeExpired {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub recipient: Pubkey,
+    pub end_at: i64,
+    pub execution_count: u32,
+    pub timestamp: i64,
+}
+
+/// Emitted when an expired scheduled charge is closed
+#[event]
+pub struct ScheduledChargeClosed {
+    pub scheduled_charge: Pubkey,
+    pub authority: Pubkey,
+    pub closed_by: Pubkey,
+    pub vault_refund: u64,
+    pub unreleased_allowance: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a merchant creates a subscription plan
+#[event]
+pub struct PlanCreated {
+    pub plan: Pubkey,
+    pub index: u64,
+    pub merchant: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub recurrence: Recurrence,
+    pub trial_seconds: Option<u64>,
+    pub max_executions: Option<u32>,
+    pub timestamp: i64,
+}
+
+/// Emitted when a merchant opens or closes a plan to new subscribers
+#[event]
+pub struct PlanUpdated {
+    pub plan: Pubkey,
+    pub merchant: Pubkey,
+    pub active: bool,
+    pub timestamp: i64,
+}
+
+/// Emitted when a subscriber signs up to a plan
+#[event]
+pub struct PlanSubscribed {
+    pub plan: Pubkey,
+    pub scheduled_charge: Pubkey,
+    pub subscriber: Pubkey,
+    pub merchant: Pubkey,
+    pub amount: u64,
+    pub trial_ends_at: Option<i64>,
+    pub execute_at: i64,
+    pub subscriptions_created: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a subscription moves to another plan
+#[event]
+pub struct ScheduledChargePlanChanged {
+    pub scheduled_charge: Pubkey,
+    pub subscriber: Pubkey,
+    pub old_plan: Pubkey,
+    pub new_plan: Pubkey,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub proration: i64,
+    pub settlement: ProrationSettlement,
+    pub settled_now: u64,
+    pub carried_adjustment: i64,
+    pub timestamp: i64,
+}
@@ -1,5 +1,11 @@
 pub mod payment_request;
+pub mod plan;
 pub mod scheduled_charge;
+pub mod split_request;
+pub mod user_profile;
 
 pub use payment_request::*;
+pub use plan::*;
 pub use scheduled_charge::*;
+pub use split_request::*;
+pub use user_profile::*;
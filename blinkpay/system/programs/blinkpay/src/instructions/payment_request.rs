@@ -2,18 +2,29 @@ use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::Token;
 
+use crate::emit_event;
 use crate::errors::BlinkPayError;
-use crate::state::{PaymentRequest, PaymentRequestStatus};
+use crate::events::*;
+use crate::state::{PaymentContribution, PaymentRequest, PaymentRequestStatus, UserProfile};
 use crate::utils::*;
 
 /// Accounts required for creating a payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-#[instruction(amount: u64, token_mint: Pubkey, recipient: Pubkey, memo: String, current_time: i64)]
 pub struct CreatePaymentRequest<'info> {
     /// The authority creating the payment request (payer)
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    /// The authority's profile, its counter indexes the new payment request
+    #[account(
+        mut,
+        seeds = [b"user_profile", authority.key().as_ref()],
+        bump = user_profile.bump,
+        has_one = authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
     /// The payment request account to be created
     #[account(
         init,
@@ -22,9 +33,7 @@ pub struct CreatePaymentRequest<'info> {
         seeds = [
             b"payment_request",
             authority.key().as_ref(),
-            recipient.as_ref(),
-            &amount.to_le_bytes(),
-            &current_time.to_le_bytes()
+            &user_profile.payment_request_count.to_le_bytes()
         ],
         bump
     )]
@@ -38,6 +47,7 @@ pub struct CreatePaymentRequest<'info> {
 }
 
 /// Accounts required for paying a payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct PayRequest<'info> {
     /// The payer fulfilling the payment request
@@ -47,9 +57,11 @@ pub struct PayRequest<'info> {
     /// The payment request account
     #[account(
         mut,
-        constraint = payment_request.status == PaymentRequestStatus::Pending @ BlinkPayError::PaymentRequestNotPending,
+        constraint = payment_request.status == PaymentRequestStatus::Pending
+            || payment_request.status == PaymentRequestStatus::PartiallyPaid @ BlinkPayError::PaymentRequestNotPending,
         constraint = payment_request.status != PaymentRequestStatus::Paid @ BlinkPayError::PaymentRequestAlreadyPaid,
         constraint = payment_request.status != PaymentRequestStatus::Cancelled @ BlinkPayError::PaymentRequestCancelled,
+        constraint = payment_request.designated_payer.is_none_or(|designated| designated == payer.key()) @ BlinkPayError::UnauthorizedPayer,
     )]
     pub payment_request: Account<'info, PaymentRequest>,
 
@@ -74,6 +86,21 @@ pub struct PayRequest<'info> {
     )]
     pub recipient_token_account: Option<AccountInfo<'info>>,
 
+    /// Escrow vault PDA holding the funds (for escrowed requests)
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", payment_request.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<SystemAccount<'info>>,
+
+    /// Escrow vault's token account (for escrowed SPL token payments)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&payment_request.token_mint)
+    )]
+    pub escrow_token_account: Option<AccountInfo<'info>>,
+
     /// Token program (for SPL token payments)
     pub token_program: Option<Program<'info, Token>>,
 
@@ -84,89 +111,664 @@ pub struct PayRequest<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Accounts required for releasing or refunding an escrowed payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SettleEscrow<'info> {
+    /// The party settling the escrow
+    pub signer: Signer<'info>,
+
+    /// The payment request account
+    #[account(
+        mut,
+        constraint = payment_request.status == PaymentRequestStatus::Escrowed @ BlinkPayError::PaymentRequestNotEscrowed,
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+
+    /// Escrow vault PDA holding the funds
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", payment_request.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: SystemAccount<'info>,
+
+    /// Destination SOL account, the recipient on release or the payer on refund (for SOL payments)
+    #[account(
+        mut,
+        constraint = is_sol_token(&payment_request.token_mint)
+    )]
+    pub destination: Option<AccountInfo<'info>>,
+
+    /// Escrow vault's token account (for SPL token payments)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&payment_request.token_mint)
+    )]
+    pub escrow_token_account: Option<AccountInfo<'info>>,
+
+    /// Destination token account, owned by the recipient on release or the payer on refund (for SPL token payments)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&payment_request.token_mint)
+    )]
+    pub destination_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token payments)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL payments)
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for refunding a paid payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RefundPaymentRequest<'info> {
+    /// The recipient sending the refund
+    #[account(
+        mut,
+        constraint = recipient.key() == payment_request.recipient @ BlinkPayError::InvalidRecipient
+    )]
+    pub recipient: Signer<'info>,
+
+    /// The payment request account
+    #[account(
+        mut,
+        constraint = payment_request.status == PaymentRequestStatus::Paid
+            || payment_request.status == PaymentRequestStatus::PartiallyRefunded
+            || payment_request.status == PaymentRequestStatus::Cancelled @ BlinkPayError::PaymentRequestNotRefundable,
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+
+    /// Refunded payer's SOL account (for SOL refunds)
+    #[account(
+        mut,
+        constraint = is_sol_token(&payment_request.token_mint)
+    )]
+    pub payer: Option<AccountInfo<'info>>,
+
+    /// Recipient's token account (for SPL token refunds)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&payment_request.token_mint)
+    )]
+    pub recipient_token_account: Option<AccountInfo<'info>>,
+
+    /// Refunded payer's token account (for SPL token refunds)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&payment_request.token_mint)
+    )]
+    pub payer_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token refunds)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL refunds)
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for cancelling a payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CancelPaymentRequest<'info> {
+    /// The authority cancelling the request (must be the creator)
+    #[account(
+        constraint = authority.key() == payment_request.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The payment request account
+    #[account(
+        mut,
+        constraint = payment_request.status == PaymentRequestStatus::Pending
+            || payment_request.status == PaymentRequestStatus::PartiallyPaid @ BlinkPayError::PaymentRequestNotPending,
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+}
+
+/// Accounts required for closing a settled payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClosePaymentRequest<'info> {
+    /// The authority closing the request (must be the creator, receives the rent)
+    #[account(
+        mut,
+        constraint = authority.key() == payment_request.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The payment request account, a cancelled one only once its payers are refunded
+    #[account(
+        mut,
+        constraint = payment_request.status == PaymentRequestStatus::Paid
+            || payment_request.status == PaymentRequestStatus::Refunded
+            || (payment_request.status == PaymentRequestStatus::Cancelled
+                && payment_request.refundable_amount() == 0) @ BlinkPayError::PaymentRequestNotSettled,
+        close = authority
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+}
+
+/// Accounts required for closing an expired payment request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseExpiredPaymentRequest<'info> {
+    /// Anyone can close an expired request
+    pub closer: Signer<'info>,
+
+    /// The authority of the payment request (receives the rent)
+    #[account(
+        mut,
+        address = payment_request.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: SystemAccount<'info>,
+
+    /// The payment request account, only while nothing has been paid towards it
+    #[account(
+        mut,
+        constraint = payment_request.status == PaymentRequestStatus::Pending
+            && payment_request.amount_paid == 0 @ BlinkPayError::PaymentRequestNotPending,
+        close = authority
+    )]
+    pub payment_request: Account<'info, PaymentRequest>,
+}
+
 /// Create a new payment request
 /// This allows users to request payments that can be fulfilled by anyone
+#[allow(clippy::too_many_arguments)]
 pub fn create_payment_request(
     ctx: Context<CreatePaymentRequest>,
     amount: u64,
     token_mint: Pubkey,
     recipient: Pubkey,
     memo: String,
-    current_time: i64,
+    expires_at: Option<i64>,
+    allow_partial: bool,
+    designated_payer: Option<Pubkey>,
+    escrow_timeout_seconds: Option<u64>,
 ) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+
     // SECURITY: Comprehensive input validation
     validate_amount(amount)?;
     validate_token_mint(&token_mint)?;
     validate_memo(&memo)?;
-    validate_recipient_not_authority(&recipient, &ctx.accounts.authority.key)?;
+    if let Some(expires_at) = expires_at {
+        validate_expiry(expires_at, current_time)?;
+    }
+    if let Some(timeout) = escrow_timeout_seconds {
+        validate_escrow_terms(amount, &token_mint, allow_partial, timeout)?;
+    }
+    validate_recipient_not_authority(&recipient, ctx.accounts.authority.key)?;
 
     // Additional security checks
     if ctx.accounts.authority.key == &recipient {
         return err!(BlinkPayError::InvalidRecipient);
     }
 
+    // Advance the authority's counter so the next account gets a fresh index
+    let user_profile = &mut ctx.accounts.user_profile;
+    let index = user_profile.payment_request_count;
+    user_profile.payment_request_count = index.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
     let payment_request = &mut ctx.accounts.payment_request;
 
     // Initialize the payment request
     payment_request.authority = *ctx.accounts.authority.key;
+    payment_request.index = index;
     payment_request.recipient = recipient;
     payment_request.amount = amount;
+    payment_request.amount_paid = 0;
+    payment_request.amount_refunded = 0;
+    payment_request.allow_partial = allow_partial;
+    payment_request.designated_payer = designated_payer;
+    payment_request.paid_by = None;
+    payment_request.paid_at = None;
+    payment_request.payments = Vec::new();
+    payment_request.escrow_timeout_seconds = escrow_timeout_seconds;
+    payment_request.escrow_expires_at = None;
     payment_request.token_mint = token_mint;
     payment_request.memo = memo;
     payment_request.created_at = current_time;
+    payment_request.expires_at = expires_at;
     payment_request.status = PaymentRequestStatus::Pending;
     payment_request.bump = ctx.bumps.payment_request;
 
     msg!("Payment request created: {} lamports/tokens to {}", amount, recipient);
 
+    emit_event!(ctx, PaymentRequestCreated {
+        payment_request: payment_request.key(),
+        index,
+        authority: payment_request.authority,
+        recipient,
+        token_mint,
+        amount,
+        expires_at,
+        allow_partial,
+        designated_payer,
+        escrow_timeout_seconds,
+        timestamp: current_time,
+    });
+
     Ok(())
 }
 
 /// Pay a payment request
-/// Anyone can pay a pending payment request to fulfill it
+/// Anyone can pay a pending payment request to fulfill it, unless a designated payer is set
 pub fn pay_request(ctx: Context<PayRequest>) -> Result<()> {
+    // Settle whatever is still outstanding
+    let amount = ctx.accounts.payment_request.outstanding_amount();
+
+    process_payment(ctx, amount)
+}
+
+/// Pay part of a payment request
+/// Only allowed on requests created with partial payments enabled
+pub fn pay_request_partial(ctx: Context<PayRequest>, amount: u64) -> Result<()> {
+    let payment_request = &ctx.accounts.payment_request;
+
+    if !payment_request.allow_partial {
+        return err!(BlinkPayError::PartialPaymentsNotAllowed);
+    }
+
+    validate_amount(amount)?;
+    if amount > payment_request.outstanding_amount() {
+        return err!(BlinkPayError::PaymentExceedsOutstanding);
+    }
+
+    process_payment(ctx, amount)
+}
+
+/// Transfer a payment from the payer to the recipient, or into escrow, and update the request
+fn process_payment(ctx: Context<PayRequest>, amount: u64) -> Result<()> {
     let payment_request = &mut ctx.accounts.payment_request;
-    let amount = payment_request.amount;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // Reject payments after the expiry deadline
+    if payment_request.is_expired(current_time) {
+        return err!(BlinkPayError::PaymentRequestExpired);
+    }
 
-    // Mark as paid first to prevent reentrancy
-    payment_request.status = PaymentRequestStatus::Paid;
+    // Record each payer's contribution so refunds go back to whoever sent the funds
+    let payer_key = ctx.accounts.payer.key();
+    match payment_request.payments.iter_mut().find(|payment| payment.payer == payer_key) {
+        Some(payment) => payment.amount = safe_add(payment.amount, amount)?,
+        None => {
+            if payment_request.payments.len() >= MAX_REQUEST_PAYERS {
+                return err!(BlinkPayError::TooManyPayers);
+            }
+            payment_request.payments.push(PaymentContribution {
+                payer: payer_key,
+                amount,
+                amount_refunded: 0,
+            });
+        }
+    }
+
+    // Update status first to prevent reentrancy
+    payment_request.amount_paid = safe_add(payment_request.amount_paid, amount)?;
+    payment_request.paid_by = Some(payer_key);
+    payment_request.paid_at = Some(current_time);
+    if let Some(timeout) = payment_request.escrow_timeout_seconds {
+        // Funds stay in the vault until released or refunded
+        payment_request.status = PaymentRequestStatus::Escrowed;
+        payment_request.escrow_expires_at = Some(
+            current_time.checked_add(timeout as i64).ok_or(BlinkPayError::Overflow)?,
+        );
+    } else if payment_request.outstanding_amount() == 0 {
+        payment_request.status = PaymentRequestStatus::Paid;
+    } else {
+        payment_request.status = PaymentRequestStatus::PartiallyPaid;
+    }
+    let escrowed = payment_request.status == PaymentRequestStatus::Escrowed;
 
     if is_sol_token(&payment_request.token_mint) {
-        // SOL payment
-        let recipient = ctx.accounts.recipient.as_ref()
-            .ok_or(BlinkPayError::InvalidRecipient)?;
+        // SOL payment, either to the recipient or into the escrow vault
+        let destination = if escrowed {
+            ctx.accounts.escrow_vault.as_ref()
+                .ok_or(BlinkPayError::InvalidEscrowVault)?
+                .to_account_info()
+        } else {
+            ctx.accounts.recipient.as_ref()
+                .ok_or(BlinkPayError::InvalidRecipient)?
+                .clone()
+        };
 
         transfer_sol(
             &ctx.accounts.payer.to_account_info(),
-            recipient,
+            &destination,
             amount,
             &ctx.accounts.system_program.to_account_info(),
         )?;
 
-        msg!("SOL payment completed: {} lamports to {}", amount, payment_request.recipient);
+        msg!("SOL payment completed: {} lamports to {}", amount, destination.key());
     } else {
         // SPL token payment
         let payer_token_account = ctx.accounts.payer_token_account.as_ref()
             .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
-        let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
-            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
         let token_program = ctx.accounts.token_program.as_ref()
             .ok_or(BlinkPayError::InvalidTokenMint)?;
 
+        let destination_token_account = if escrowed {
+            let escrow_vault = ctx.accounts.escrow_vault.as_ref()
+                .ok_or(BlinkPayError::InvalidEscrowVault)?;
+            let escrow_token_account = ctx.accounts.escrow_token_account.as_ref()
+                .ok_or(BlinkPayError::InvalidEscrowVault)?;
+
+            // The vault token account must be held by the escrow PDA in the request's mint
+            validate_token_account_ownership(escrow_token_account, &escrow_vault.key())?;
+            validate_token_account_mint(escrow_token_account, &payment_request.token_mint)?;
+            escrow_token_account
+        } else {
+            let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+                .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+
+            // SECURITY: Payments must land in the recipient's own account for the request's mint
+            validate_token_account_ownership(recipient_token_account, &payment_request.recipient)?;
+            validate_token_account_mint(recipient_token_account, &payment_request.token_mint)?;
+            recipient_token_account
+        };
+
         // Validate token account ownership
         validate_token_account_ownership(payer_token_account, &ctx.accounts.payer.key())?;
 
         // Transfer tokens
         transfer_spl_tokens(
             payer_token_account,
-            recipient_token_account,
+            destination_token_account,
             &ctx.accounts.payer.to_account_info(),
             &token_program.to_account_info(),
             amount,
         )?;
 
-        msg!("SPL token payment completed: {} tokens to {}", amount, payment_request.recipient);
+        msg!("SPL token payment completed: {} tokens to {}", amount, destination_token_account.key());
+    }
+
+    emit_event!(ctx, PaymentRequestPaid {
+        payment_request: payment_request.key(),
+        payer: ctx.accounts.payer.key(),
+        recipient: payment_request.recipient,
+        token_mint: payment_request.token_mint,
+        amount,
+        amount_paid: payment_request.amount_paid,
+        status: payment_request.status.clone(),
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Release an escrowed payment request
+/// The payer or the authority can release the escrowed funds to the recipient
+pub fn release_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+    let payment_request = &ctx.accounts.payment_request;
+    let signer = ctx.accounts.signer.key();
+
+    if payment_request.paid_by != Some(signer) && payment_request.authority != signer {
+        return err!(BlinkPayError::InvalidAuthority);
+    }
+
+    let recipient = payment_request.recipient;
+    settle_escrow(ctx, recipient, PaymentRequestStatus::Paid)
+}
+
+/// Refund an escrowed payment request
+/// The authority can refund the payer at any time, anyone can once the escrow timeout has passed
+pub fn refund_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+    let payment_request = &ctx.accounts.payment_request;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    if payment_request.authority != ctx.accounts.signer.key()
+        && !payment_request.is_escrow_expired(current_time)
+    {
+        return err!(BlinkPayError::EscrowNotExpired);
     }
 
+    let payer = payment_request.paid_by.ok_or(BlinkPayError::PaymentRequestNotEscrowed)?;
+    settle_escrow(ctx, payer, PaymentRequestStatus::Refunded)
+}
+
+/// Move the escrowed funds out of the vault to `destination_owner` and update the request
+fn settle_escrow(
+    ctx: Context<SettleEscrow>,
+    destination_owner: Pubkey,
+    status: PaymentRequestStatus,
+) -> Result<()> {
+    let payment_request = &mut ctx.accounts.payment_request;
+    let amount = payment_request.amount_paid;
+    let payment_request_key = payment_request.key();
+    let released = status == PaymentRequestStatus::Paid;
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"escrow_vault",
+        payment_request_key.as_ref(),
+        &[ctx.bumps.escrow_vault],
+    ]];
+
+    // Update status first to prevent reentrancy
+    if status == PaymentRequestStatus::Refunded {
+        payment_request.amount_refunded = amount;
+    }
+    payment_request.status = status;
+    payment_request.escrow_expires_at = None;
+
+    if is_sol_token(&payment_request.token_mint) {
+        // SOL payment out of the vault
+        let destination = ctx.accounts.destination.as_ref()
+            .ok_or(BlinkPayError::InvalidRecipient)?;
+        if destination.key() != destination_owner {
+            return err!(BlinkPayError::InvalidRecipient);
+        }
+
+        // SECURITY: Sweep the whole vault, lamports sent to it after funding would otherwise
+        // leave it below the rent-exempt minimum and every settlement would fail
+        let escrow_vault = ctx.accounts.escrow_vault.to_account_info();
+        let vault_balance = escrow_vault.lamports();
+
+        transfer_sol_signed(
+            &escrow_vault,
+            destination,
+            vault_balance,
+            &ctx.accounts.system_program.to_account_info(),
+            signer_seeds,
+        )?;
+
+        msg!("Escrow settled: {} lamports to {}", vault_balance, destination_owner);
+    } else {
+        // SPL token payment out of the vault
+        let escrow_token_account = ctx.accounts.escrow_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidEscrowVault)?;
+        let destination_token_account = ctx.accounts.destination_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+        let token_program = ctx.accounts.token_program.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+        // Validate token account ownership
+        validate_token_account_ownership(escrow_token_account, &ctx.accounts.escrow_vault.key())?;
+        validate_token_account_ownership(destination_token_account, &destination_owner)?;
+
+        // Transfer tokens
+        transfer_spl_tokens_signed(
+            escrow_token_account,
+            destination_token_account,
+            &ctx.accounts.escrow_vault.to_account_info(),
+            &token_program.to_account_info(),
+            amount,
+            signer_seeds,
+        )?;
+
+        msg!("Escrow settled: {} tokens to {}", amount, destination_owner);
+    }
+
+    let settled_by = ctx.accounts.signer.key();
+    let token_mint = payment_request.token_mint;
+    let timestamp = Clock::get()?.unix_timestamp;
+    if released {
+        emit_event!(ctx, EscrowReleased {
+            payment_request: payment_request_key,
+            released_by: settled_by,
+            recipient: destination_owner,
+            token_mint,
+            amount,
+            timestamp,
+        });
+    } else {
+        emit_event!(ctx, EscrowRefunded {
+            payment_request: payment_request_key,
+            refunded_by: settled_by,
+            payer: destination_owner,
+            token_mint,
+            amount,
+            timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+/// Refund a paid payment request
+/// The recipient sends all or part of a payer's contribution back to that payer
+pub fn refund_payment_request(
+    ctx: Context<RefundPaymentRequest>,
+    payer_key: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let payment_request = &mut ctx.accounts.payment_request;
+
+    validate_amount(amount)?;
+
+    // SECURITY: A payer can only get back what they paid themselves
+    let payment = payment_request.payments.iter_mut()
+        .find(|payment| payment.payer == payer_key)
+        .ok_or(BlinkPayError::UnauthorizedPayer)?;
+    if amount > payment.refundable_amount() {
+        return err!(BlinkPayError::RefundExceedsPaid);
+    }
+
+    // Update status first to prevent reentrancy
+    payment.amount_refunded = safe_add(payment.amount_refunded, amount)?;
+    payment_request.amount_refunded = safe_add(payment_request.amount_refunded, amount)?;
+    // Cancelled requests stay cancelled while their partial payments are returned
+    if payment_request.status != PaymentRequestStatus::Cancelled {
+        payment_request.status = if payment_request.refundable_amount() == 0 {
+            PaymentRequestStatus::Refunded
+        } else {
+            PaymentRequestStatus::PartiallyRefunded
+        };
+    }
+
+    if is_sol_token(&payment_request.token_mint) {
+        // SOL refund
+        let payer = ctx.accounts.payer.as_ref()
+            .ok_or(BlinkPayError::UnauthorizedPayer)?;
+        if payer.key() != payer_key {
+            return err!(BlinkPayError::UnauthorizedPayer);
+        }
+
+        transfer_sol(
+            &ctx.accounts.recipient.to_account_info(),
+            payer,
+            amount,
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        msg!("SOL refund completed: {} lamports to {}", amount, payer_key);
+    } else {
+        // SPL token refund
+        let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        let payer_token_account = ctx.accounts.payer_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+        let token_program = ctx.accounts.token_program.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+        // Validate token account ownership and mint
+        validate_token_account_ownership(recipient_token_account, &ctx.accounts.recipient.key())?;
+        validate_token_account_ownership(payer_token_account, &payer_key)?;
+        validate_token_account_mint(payer_token_account, &payment_request.token_mint)?;
+
+        // Transfer tokens
+        transfer_spl_tokens(
+            recipient_token_account,
+            payer_token_account,
+            &ctx.accounts.recipient.to_account_info(),
+            &token_program.to_account_info(),
+            amount,
+        )?;
+
+        msg!("SPL token refund completed: {} tokens to {}", amount, payer_key);
+    }
+
+    emit_event!(ctx, PaymentRequestRefunded {
+        payment_request: payment_request.key(),
+        recipient: payment_request.recipient,
+        payer: payer_key,
+        token_mint: payment_request.token_mint,
+        amount,
+        amount_refunded: payment_request.amount_refunded,
+        status: payment_request.status.clone(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Cancel a payment request
+/// Only the authority can cancel their own pending requests
+pub fn cancel_payment_request(ctx: Context<CancelPaymentRequest>) -> Result<()> {
+    let payment_request = &mut ctx.accounts.payment_request;
+
+    // Mark as cancelled so it can no longer be paid
+    payment_request.status = PaymentRequestStatus::Cancelled;
+
+    msg!("Payment request cancelled by authority");
+
+    emit_event!(ctx, PaymentRequestCancelled {
+        payment_request: payment_request.key(),
+        authority: payment_request.authority,
+        amount_paid: payment_request.amount_paid,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Close a paid or cancelled payment request
+/// Returns the account rent to the authority
+pub fn close_payment_request(ctx: Context<ClosePaymentRequest>) -> Result<()> {
+    msg!("Payment request closed, rent returned to authority");
+
+    emit_event!(ctx, PaymentRequestClosed {
+        payment_request: ctx.accounts.payment_request.key(),
+        authority: ctx.accounts.authority.key(),
+        closed_by: ctx.accounts.authority.key(),
+        status: ctx.accounts.payment_request.status.clone(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Close an expired payment request
+/// Anyone can close a pending request past its expiry, rent goes back to the authority
+pub fn close_expired_payment_request(ctx: Context<CloseExpiredPaymentRequest>) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+
+    if !ctx.accounts.payment_request.is_expired(current_time) {
+        return err!(BlinkPayError::PaymentRequestNotExpired);
+    }
+
+    msg!("Expired payment request closed, rent returned to authority");
+
+    emit_event!(ctx, PaymentRequestClosed {
+        payment_request: ctx.accounts.payment_request.key(),
+        authority: ctx.accounts.authority.key(),
+        closed_by: ctx.accounts.closer.key(),
+        status: ctx.accounts.payment_request.status.clone(),
+        timestamp: current_time,
+    });
+
     Ok(())
 }
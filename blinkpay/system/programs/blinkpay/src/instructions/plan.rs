@@ -0,0 +1,528 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+use crate::emit_event;
+use crate::errors::BlinkPayError;
+use crate::events::*;
+use crate::instructions::scheduled_charge::{required_allowance, sync_delegate_allowance, validate_max_executions_remaining};
+use crate::schedule::previous_occurrence;
+use crate::state::{CatchUpPolicy, ChargeFundingMode, Plan, ProrationSettlement, Recurrence, ScheduledCharge, ScheduledChargeStatus, ScheduledChargeType, UserProfile};
+use crate::utils::*;
+
+/// Accounts required for creating a subscription plan
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CreatePlan<'info> {
+    /// The merchant offering the plan
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    /// The merchant's profile, its counter indexes the new plan
+    #[account(
+        mut,
+        seeds = [b"user_profile", merchant.key().as_ref()],
+        bump = user_profile.bump,
+        constraint = user_profile.authority == merchant.key() @ BlinkPayError::InvalidAuthority
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    /// The plan account to be created
+    #[account(
+        init,
+        payer = merchant,
+        space = Plan::LEN,
+        seeds = [
+            b"plan",
+            merchant.key().as_ref(),
+            &user_profile.plan_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub plan: Account<'info, Plan>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for opening or closing a plan to new subscribers
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetPlanActive<'info> {
+    /// The merchant that owns the plan
+    pub merchant: Signer<'info>,
+
+    /// The plan account
+    #[account(
+        mut,
+        has_one = merchant @ BlinkPayError::InvalidAuthority
+    )]
+    pub plan: Account<'info, Plan>,
+}
+
+/// Accounts required for subscribing to a plan
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct Subscribe<'info> {
+    /// The subscriber paying for the plan
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+
+    /// The subscriber's profile, its counter indexes the new scheduled charge
+    #[account(
+        mut,
+        seeds = [b"user_profile", subscriber.key().as_ref()],
+        bump = user_profile.bump,
+        constraint = user_profile.authority == subscriber.key() @ BlinkPayError::InvalidAuthority
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    /// The plan being subscribed to
+    #[account(
+        mut,
+        constraint = plan.active @ BlinkPayError::PlanInactive
+    )]
+    pub plan: Account<'info, Plan>,
+
+    /// The subscriber's scheduled charge, bound to the plan
+    #[account(
+        init,
+        payer = subscriber,
+        space = ScheduledCharge::LEN,
+        seeds = [
+            b"scheduled_charge",
+            subscriber.key().as_ref(),
+            &user_profile.scheduled_charge_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+
+    /// Subscriber's token account the charge pulls from (for SPL token plans)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&plan.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Program delegate approved to pull charges from the subscriber's token account (for SPL token plans)
+    #[account(
+        seeds = [b"charge_delegate", subscriber.key().as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Token program (for SPL token plans)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for moving a subscription to another plan
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ChangePlan<'info> {
+    /// The subscriber changing plans (must be the charge authority)
+    #[account(
+        mut,
+        constraint = subscriber.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub subscriber: Signer<'info>,
+
+    /// The subscription's scheduled charge
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending @ BlinkPayError::ScheduledChargeNotPending,
+        constraint = scheduled_charge.plan == current_plan.key() @ BlinkPayError::InvalidPlanChange,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+
+    /// The plan the subscription is on
+    pub current_plan: Account<'info, Plan>,
+
+    /// The plan the subscription moves to
+    #[account(
+        constraint = new_plan.active @ BlinkPayError::PlanInactive,
+        constraint = new_plan.key() != current_plan.key() @ BlinkPayError::InvalidPlanChange,
+        constraint = new_plan.merchant == current_plan.merchant @ BlinkPayError::InvalidPlanChange,
+        constraint = new_plan.token_mint == current_plan.token_mint @ BlinkPayError::InvalidPlanChange,
+        constraint = scheduled_charge.recurrence.as_ref() == Some(&new_plan.recurrence) @ BlinkPayError::InvalidPlanChange,
+    )]
+    pub new_plan: Account<'info, Plan>,
+
+    /// Merchant's SOL account receiving an immediate settlement (for SOL plans)
+    #[account(
+        mut,
+        constraint = is_sol_token(&scheduled_charge.token_mint) && recipient.key() == scheduled_charge.recipient
+    )]
+    pub recipient: Option<AccountInfo<'info>>,
+
+    /// Subscriber's token account the settlement and future charges are pulled from (for SPL token plans)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Merchant's token account receiving an immediate settlement (for SPL token plans)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub recipient_token_account: Option<AccountInfo<'info>>,
+
+    /// Program delegate approved to pull charges from the subscriber's token account (for SPL token plans)
+    #[account(
+        seeds = [b"charge_delegate", subscriber.key().as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Token program (for SPL token plans)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL settlements)
+    pub system_program: Program<'info, System>,
+}
+
+/// Create a subscription plan
+/// Merchants define the price and billing schedule once and subscribers sign up to it
+pub fn create_plan(
+    ctx: Context<CreatePlan>,
+    amount: u64,
+    token_mint: Pubkey,
+    recurrence: Recurrence,
+    trial_seconds: Option<u64>,
+    max_executions: Option<u32>,
+    memo: String,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // SECURITY: Plans must describe valid recurring charge terms
+    validate_charge_terms(amount, max_executions, &ScheduledChargeType::Recurring, Some(&recurrence))?;
+    validate_introductory_terms(amount, &ScheduledChargeType::Recurring, trial_seconds, None)?;
+    validate_token_mint(&token_mint)?;
+    validate_memo(&memo)?;
+
+    // Advance the merchant's counter so the next plan gets a fresh index
+    let user_profile = &mut ctx.accounts.user_profile;
+    let index = user_profile.plan_count;
+    user_profile.plan_count = index.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    let plan = &mut ctx.accounts.plan;
+    plan.merchant = *ctx.accounts.merchant.key;
+    plan.index = index;
+    plan.amount = amount;
+    plan.token_mint = token_mint;
+    plan.recurrence = recurrence.clone();
+    plan.trial_seconds = trial_seconds;
+    plan.max_executions = max_executions;
+    plan.memo = memo;
+    plan.subscriptions_created = 0;
+    plan.active = true;
+    plan.created_at = current_time;
+    plan.bump = ctx.bumps.plan;
+
+    msg!("Plan created: {} lamports/tokens per cycle", amount);
+
+    emit_event!(ctx, PlanCreated {
+        plan: plan.key(),
+        index,
+        merchant: plan.merchant,
+        token_mint,
+        amount,
+        recurrence,
+        trial_seconds,
+        max_executions,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Open or close a plan to new subscribers
+/// Existing subscriptions keep running either way
+pub fn set_plan_active(ctx: Context<SetPlanActive>, active: bool) -> Result<()> {
+    let plan = &mut ctx.accounts.plan;
+    plan.active = active;
+
+    msg!("Plan {} to new subscribers", if active { "opened" } else { "closed" });
+
+    emit_event!(ctx, PlanUpdated {
+        plan: plan.key(),
+        merchant: plan.merchant,
+        active,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Subscribe to a plan
+/// Creates a scheduled charge from the plan's terms, billed from now with any trial cycles free
+pub fn subscribe(ctx: Context<Subscribe>) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let plan = &ctx.accounts.plan;
+
+    // SECURITY: Merchants cannot subscribe to their own plans
+    validate_recipient_not_authority(&plan.merchant, ctx.accounts.subscriber.key)?;
+
+    // The trial runs from the moment of subscribing
+    let execute_at = current_time;
+    let trial_ends_at = match plan.trial_seconds {
+        Some(trial_seconds) => Some(execute_at.checked_add(trial_seconds as i64)
+            .ok_or(BlinkPayError::Overflow)?),
+        None => None,
+    };
+
+    // Advance the subscriber's counter so the next account gets a fresh index
+    let user_profile = &mut ctx.accounts.user_profile;
+    let index = user_profile.scheduled_charge_count;
+    user_profile.scheduled_charge_count = index.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+
+    // Initialize the scheduled charge from the plan's terms
+    scheduled_charge.authority = *ctx.accounts.subscriber.key;
+    scheduled_charge.index = index;
+    scheduled_charge.recipient = plan.merchant;
+    scheduled_charge.plan = plan.key();
+    scheduled_charge.amount = plan.amount;
+    scheduled_charge.keeper_tip = 0;
+    scheduled_charge.trial_ends_at = trial_ends_at;
+    scheduled_charge.intro_offer = None;
+    scheduled_charge.meter = None;
+    scheduled_charge.proration_adjustment = 0;
+    scheduled_charge.token_mint = plan.token_mint;
+    scheduled_charge.charge_type = ScheduledChargeType::Recurring;
+    scheduled_charge.funding_mode = ChargeFundingMode::Direct;
+    scheduled_charge.funding_token_account = ctx.accounts.authority_token_account.as_ref()
+        .map_or(Pubkey::default(), |token_account| token_account.key());
+    scheduled_charge.delegated_allowance = 0;
+    scheduled_charge.execute_at = execute_at;
+    scheduled_charge.recurrence = Some(plan.recurrence.clone());
+    scheduled_charge.catch_up_policy = CatchUpPolicy::SkipMissed;
+    scheduled_charge.pending_amendment = None;
+    scheduled_charge.auto_resume_at = None;
+    scheduled_charge.last_executed_at = None;
+    scheduled_charge.max_executions = plan.max_executions;
+    scheduled_charge.end_at = None;
+    scheduled_charge.grace_period = DEFAULT_GRACE_PERIOD_SECONDS;
+    scheduled_charge.failed_attempts = 0;
+    scheduled_charge.last_failure_at = None;
+    scheduled_charge.execution_count = 0;
+    scheduled_charge.memo = plan.memo.clone();
+    scheduled_charge.created_at = current_time;
+    scheduled_charge.status = ScheduledChargeStatus::Pending;
+    scheduled_charge.bump = ctx.bumps.scheduled_charge;
+
+    // Keepers pull the subscription without the subscriber signing, so approve every cycle now
+    let allowance = required_allowance(scheduled_charge);
+    sync_delegate_allowance(
+        scheduled_charge,
+        allowance,
+        &ctx.accounts.subscriber.to_account_info(),
+        ctx.accounts.authority_token_account.as_ref(),
+        ctx.accounts.charge_delegate.as_ref(),
+        ctx.accounts.token_program.as_ref(),
+    )?;
+
+    let plan = &mut ctx.accounts.plan;
+    plan.subscriptions_created = plan.subscriptions_created.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    msg!("Subscribed to plan {}, subscription #{}", plan.key(), plan.subscriptions_created);
+
+    emit_event!(ctx, PlanSubscribed {
+        plan: plan.key(),
+        scheduled_charge: scheduled_charge.key(),
+        subscriber: scheduled_charge.authority,
+        merchant: plan.merchant,
+        amount: plan.amount,
+        trial_ends_at,
+        execute_at,
+        subscriptions_created: plan.subscriptions_created,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Move a subscription to another plan of the same merchant
+/// The price difference for the rest of the current period is settled now or carried into the next execution
+pub fn change_plan(ctx: Context<ChangePlan>, settlement: ProrationSettlement) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let new_plan = &ctx.accounts.new_plan;
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+
+    // SECURITY: The new plan's cycle cap must leave at least one execution
+    validate_max_executions_remaining(scheduled_charge, new_plan.max_executions)?;
+
+    let proration = plan_change_proration(
+        scheduled_charge.amount,
+        new_plan.amount,
+        &ctx.accounts.current_plan.recurrence,
+        scheduled_charge.execute_at,
+        scheduled_charge.last_executed_at,
+        scheduled_charge.trial_ends_at,
+        current_time,
+    )?;
+
+    // The merchant does not sign here, so only debits can be settled now
+    let settled_now = match settlement {
+        ProrationSettlement::Immediate if proration > 0 => proration as u64,
+        _ => 0,
+    };
+    let carried_adjustment = scheduled_charge.proration_adjustment
+        .checked_add(proration - settled_now as i64)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    // SECURITY: Update state BEFORE transfer (Checks-Effects-Interactions pattern)
+    let old_plan = scheduled_charge.plan;
+    let old_amount = scheduled_charge.amount;
+    scheduled_charge.plan = new_plan.key();
+    scheduled_charge.amount = new_plan.amount;
+    scheduled_charge.max_executions = new_plan.max_executions;
+    scheduled_charge.memo = new_plan.memo.clone();
+    scheduled_charge.proration_adjustment = carried_adjustment;
+
+    // Future executions and any carried debit are pulled through the delegate at the new price
+    let allowance = required_allowance(scheduled_charge);
+    sync_delegate_allowance(
+        scheduled_charge,
+        allowance,
+        &ctx.accounts.subscriber.to_account_info(),
+        ctx.accounts.authority_token_account.as_ref(),
+        ctx.accounts.charge_delegate.as_ref(),
+        ctx.accounts.token_program.as_ref(),
+    )?;
+
+    if settled_now > 0 {
+        if is_sol_token(&scheduled_charge.token_mint) {
+            let recipient = ctx.accounts.recipient.as_ref()
+                .ok_or(BlinkPayError::InvalidRecipient)?;
+
+            transfer_sol(
+                &ctx.accounts.subscriber.to_account_info(),
+                recipient,
+                settled_now,
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+        } else {
+            let authority_token_account = ctx.accounts.authority_token_account.as_ref()
+                .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+            let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+                .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+            let token_program = ctx.accounts.token_program.as_ref()
+                .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+            validate_token_account_ownership(recipient_token_account, &scheduled_charge.recipient)?;
+
+            transfer_spl_tokens(
+                authority_token_account,
+                recipient_token_account,
+                &ctx.accounts.subscriber.to_account_info(),
+                &token_program.to_account_info(),
+                settled_now,
+            )?;
+        }
+    }
+
+    msg!("Plan changed: prorated {} settled now {}, carried {}", proration, settled_now, carried_adjustment);
+
+    emit_event!(ctx, ScheduledChargePlanChanged {
+        scheduled_charge: scheduled_charge.key(),
+        subscriber: scheduled_charge.authority,
+        old_plan,
+        new_plan: scheduled_charge.plan,
+        old_amount,
+        new_amount: scheduled_charge.amount,
+        proration,
+        settlement,
+        settled_now,
+        carried_adjustment,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Prorated price difference for the rest of a subscription's current period
+/// Periods that were never billed owe nothing: before the first execution, during the trial or skipped by a pause
+fn plan_change_proration(
+    old_amount: u64,
+    new_amount: u64,
+    recurrence: &Recurrence,
+    execute_at: i64,
+    last_executed_at: Option<i64>,
+    trial_ends_at: Option<i64>,
+    current_time: i64,
+) -> Result<i64> {
+    // The period comes from the schedule, so a late execution does not shorten it
+    let period_start = previous_occurrence(recurrence, execute_at)?;
+
+    // Only the execution for this period paid for it, an earlier one paid for a period since skipped
+    let paid = last_executed_at.is_some_and(|last_executed_at| last_executed_at >= period_start)
+        && trial_ends_at.is_none_or(|trial_ends_at| period_start >= trial_ends_at);
+    if !paid {
+        return Ok(0);
+    }
+
+    Ok(prorate_price_change(
+        old_amount,
+        new_amount,
+        execute_at.saturating_sub(current_time),
+        execute_at.saturating_sub(period_start),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{first_occurrence_from, next_occurrence};
+
+    const HOUR: i64 = 3_600;
+    const HOURLY: Recurrence = Recurrence::EveryNSeconds { interval_seconds: HOUR as u64 };
+
+    #[test]
+    fn prorates_the_rest_of_a_paid_period() {
+        // Paid a little late for the slot at 0, upgrading halfway through the hour
+        let proration = plan_change_proration(100, 300, &HOURLY, HOUR, Some(60), None, HOUR / 2).unwrap();
+        assert_eq!(proration, 100);
+
+        let credit = plan_change_proration(300, 100, &HOURLY, HOUR, Some(60), None, HOUR / 2).unwrap();
+        assert_eq!(credit, -100);
+    }
+
+    #[test]
+    fn owes_nothing_after_a_pause_skipped_the_paid_period() {
+        // Executed for the slot at 0, then paused and resumed three hours later
+        let last_executed_at = 10;
+        let paused_slot = next_occurrence(&HOURLY, 0).unwrap();
+        let resumed_at = 3 * HOUR + 600;
+        let execute_at = first_occurrence_from(&HOURLY, paused_slot, resumed_at).unwrap();
+        assert_eq!(execute_at, 4 * HOUR);
+
+        let proration = plan_change_proration(
+            100,
+            300,
+            &HOURLY,
+            execute_at,
+            Some(last_executed_at),
+            None,
+            resumed_at + 60,
+        ).unwrap();
+        assert_eq!(proration, 0);
+    }
+
+    #[test]
+    fn owes_nothing_before_the_first_execution_or_during_the_trial() {
+        assert_eq!(plan_change_proration(100, 300, &HOURLY, HOUR, None, None, HOUR / 2).unwrap(), 0);
+
+        // The slot at 0 fell inside a two-hour trial and was free
+        let in_trial = plan_change_proration(100, 300, &HOURLY, HOUR, Some(0), Some(2 * HOUR), HOUR / 2).unwrap();
+        assert_eq!(in_trial, 0);
+    }
+}
@@ -2,18 +2,31 @@ use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::Token;
 
+use crate::emit_event;
 use crate::errors::BlinkPayError;
-use crate::state::{ScheduledCharge, ScheduledChargeStatus, ScheduledChargeType};
+use crate::events::*;
+use crate::schedule::{advance_periods, first_occurrence_from, next_occurrence, periods_between};
+use crate::state::{CancellationReason, CatchUpPolicy, ChargeAmendment, ChargeFundingMode, Meter, Recurrence, ScheduledCharge, ScheduledChargeParams, ScheduledChargeStatus, ScheduledChargeType, UserProfile};
 use crate::utils::*;
 
 /// Accounts required for creating a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-#[instruction(amount: u64, token_mint: Pubkey, recipient: Pubkey, execute_at: i64, charge_type: u8, interval_seconds: Option<u64>, max_executions: Option<u32>, memo: String, current_time: i64)]
+#[instruction(params: ScheduledChargeParams)]
 pub struct CreateScheduledCharge<'info> {
     /// The authority creating the scheduled charge
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    /// The authority's profile, its counter indexes the new scheduled charge
+    #[account(
+        mut,
+        seeds = [b"user_profile", authority.key().as_ref()],
+        bump = user_profile.bump,
+        has_one = authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
     /// The scheduled charge account to be created
     #[account(
         init,
@@ -22,15 +35,44 @@ pub struct CreateScheduledCharge<'info> {
         seeds = [
             b"scheduled_charge",
             authority.key().as_ref(),
-            recipient.as_ref(),
-            &amount.to_le_bytes(),
-            &execute_at.to_le_bytes(),
-            &[charge_type as u8],
+            &user_profile.scheduled_charge_count.to_le_bytes()
         ],
         bump
     )]
     pub scheduled_charge: Account<'info, ScheduledCharge>,
 
+    /// Authority's token account the charge pulls from (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&params.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Program delegate approved to pull charges from the authority's token account (for SPL token charges)
+    #[account(
+        seeds = [b"charge_delegate", authority.key().as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Vault holding the funds deposited up front (for vault-funded charges)
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: Option<SystemAccount<'info>>,
+
+    /// Vault's token account, owned by the charge vault (for vault-funded SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&params.token_mint)
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+
     /// System program for account creation
     pub system_program: Program<'info, System>,
 
@@ -39,6 +81,7 @@ pub struct CreateScheduledCharge<'info> {
 }
 
 /// Accounts required for executing a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct ExecuteScheduledCharge<'info> {
     /// The executor (can be anyone)
@@ -48,13 +91,15 @@ pub struct ExecuteScheduledCharge<'info> {
     /// The scheduled charge account
     #[account(
         mut,
-        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending @ BlinkPayError::ScheduledChargeNotPending,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused
+            || scheduled_charge.status == ScheduledChargeStatus::PastDue @ BlinkPayError::ScheduledChargeNotPending,
         constraint = scheduled_charge.status != ScheduledChargeStatus::Executed @ BlinkPayError::ScheduledChargeAlreadyExecuted,
         constraint = scheduled_charge.status != ScheduledChargeStatus::Cancelled @ BlinkPayError::ScheduledChargeCancelled,
     )]
     pub scheduled_charge: Account<'info, ScheduledCharge>,
 
-    /// Authority's SOL account (for SOL payments)
+    /// Authority's SOL account, must sign SOL payments
     #[account(
         mut,
         signer,
@@ -69,10 +114,11 @@ pub struct ExecuteScheduledCharge<'info> {
     )]
     pub recipient: Option<AccountInfo<'info>>,
 
-    /// Authority's token account (for SPL token payments)
+    /// Authority's token account the charge was approved on (for SPL token payments)
     #[account(
         mut,
-        constraint = !is_sol_token(&scheduled_charge.token_mint)
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = authority_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidTokenAccountOwner
     )]
     pub authority_token_account: Option<AccountInfo<'info>>,
 
@@ -83,6 +129,36 @@ pub struct ExecuteScheduledCharge<'info> {
     )]
     pub recipient_token_account: Option<AccountInfo<'info>>,
 
+    /// Executor's token account receiving the keeper tip (for tipped SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub executor_token_account: Option<AccountInfo<'info>>,
+
+    /// Program delegate that signs the pull from the authority's token account (for SPL token payments)
+    #[account(
+        seeds = [b"charge_delegate", scheduled_charge.authority.as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Vault the charge is paid out of (for vault-funded charges)
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: Option<SystemAccount<'info>>,
+
+    /// Vault's token account (for vault-funded SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = vault_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidChargeVault
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
+
     /// Token program (for SPL token payments)
     pub token_program: Option<Program<'info, Token>>,
 
@@ -97,181 +173,1763 @@ pub struct ExecuteScheduledCharge<'info> {
 }
 
 /// Accounts required for cancelling a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct CancelScheduledCharge<'info> {
-    /// The authority cancelling the charge (must be the creator)
+    /// The party cancelling the charge, the authority or the recipient
+    #[account(
+        constraint = canceller.key() == scheduled_charge.authority
+            || canceller.key() == scheduled_charge.recipient @ BlinkPayError::InvalidAuthority
+    )]
+    pub canceller: Signer<'info>,
+
+    /// The authority who funded the charge, receives the rent and any vault refund
     #[account(
         mut,
         constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
     )]
-    pub authority: Signer<'info>,
+    pub authority: SystemAccount<'info>,
 
-    /// The scheduled charge account
+    /// The scheduled charge account, finished charges can be cancelled to refund and close them
     #[account(
         mut,
-        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending @ BlinkPayError::ScheduledChargeNotPending,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused
+            || scheduled_charge.status == ScheduledChargeStatus::PastDue
+            || scheduled_charge.status == ScheduledChargeStatus::Suspended
+            || scheduled_charge.status == ScheduledChargeStatus::Executed
+            || scheduled_charge.status == ScheduledChargeStatus::Expired @ BlinkPayError::ScheduledChargeNotPending,
         close = authority
     )]
     pub scheduled_charge: Account<'info, ScheduledCharge>,
-}
 
-/// Create a new scheduled charge
-/// Sets up automatic payments that execute at specified times
-pub fn create_scheduled_charge(
-    ctx: Context<CreateScheduledCharge>,
-    amount: u64,
-    token_mint: Pubkey,
-    recipient: Pubkey,
-    execute_at: i64,
-    charge_type_u8: u8,
-    interval_seconds: Option<u64>,
-    max_executions: Option<u32>,
-    memo: String,
-    current_time: i64,
-) -> Result<()> {
-    // SECURITY: Convert u8 to ScheduledChargeType with bounds checking
-    let charge_type = match charge_type_u8 {
-        0 => ScheduledChargeType::OneTime,
-        1 => ScheduledChargeType::Recurring,
-        _ => return err!(BlinkPayError::InvalidTimestamp),
-    };
+    /// Authority's token account whose allowance is reduced or that receives the vault refund (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
 
-    // SECURITY: Comprehensive input validation
-    validate_scheduled_charge_params(
-        amount,
-        execute_at,
-        max_executions,
-        interval_seconds,
-        current_time,
-    )?;
-    validate_token_mint(&token_mint)?;
-    validate_memo(&memo)?;
-    validate_recipient_not_authority(&recipient, &ctx.accounts.authority.key)?;
+    /// Program delegate approved for the charge (for SPL token charges)
+    #[account(
+        seeds = [b"charge_delegate", authority.key().as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
 
-    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    /// Vault whose remaining balance is refunded (for vault-funded charges)
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: Option<SystemAccount<'info>>,
 
-    // Initialize the scheduled charge
-    scheduled_charge.authority = *ctx.accounts.authority.key;
-    scheduled_charge.recipient = recipient;
-    scheduled_charge.amount = amount;
-    scheduled_charge.token_mint = token_mint;
-    scheduled_charge.charge_type = charge_type;
-    scheduled_charge.execute_at = execute_at;
-    scheduled_charge.interval_seconds = interval_seconds;
-    scheduled_charge.last_executed_at = None;
-    scheduled_charge.max_executions = max_executions;
-    scheduled_charge.execution_count = 0;
-    scheduled_charge.memo = memo;
-    scheduled_charge.created_at = current_time;
-    scheduled_charge.status = ScheduledChargeStatus::Pending;
-    scheduled_charge.bump = ctx.bumps.scheduled_charge;
+    /// Vault's token account, closed after the refund (for vault-funded SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = vault_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidChargeVault
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
 
-    msg!("Scheduled charge created: {} lamports/tokens to {} at timestamp {}", amount, recipient, execute_at);
+    /// Token program (for SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
 
-    Ok(())
+    /// System program (for SOL vault refunds)
+    pub system_program: Program<'info, System>,
 }
 
-/// Execute a scheduled charge
-/// Can be called by anyone when the execution time has been reached
-pub fn execute_scheduled_charge(ctx: Context<ExecuteScheduledCharge>) -> Result<()> {
-    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
-    let clock = Clock::get()?;
-    let current_time = clock.unix_timestamp;
+/// Accounts required for pausing a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PauseScheduledCharge<'info> {
+    /// The authority pausing the charge (must be the creator)
+    #[account(
+        constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
 
-    // SECURITY: Restore time validation with buffer for clock skew
-    if current_time < scheduled_charge.execute_at.saturating_sub(300) { // 5 minute buffer
-        return err!(BlinkPayError::ExecutionTimeNotReached);
-    }
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending @ BlinkPayError::ScheduledChargeNotPending,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+}
 
-    // Check if already executed or cancelled
-    if scheduled_charge.status != ScheduledChargeStatus::Pending {
-        return err!(BlinkPayError::ScheduledChargeNotPending);
-    }
+/// Accounts required for resuming a paused scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ResumeScheduledCharge<'info> {
+    /// The authority resuming the charge (must be the creator)
+    #[account(
+        constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
 
-    // Check max executions for recurring charges
-    if let Some(max_exec) = scheduled_charge.max_executions {
-        if scheduled_charge.execution_count >= max_exec {
-            return err!(BlinkPayError::MaxExecutionsExceeded);
-        }
-    }
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Paused @ BlinkPayError::ScheduledChargeNotPaused,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+}
 
-    let amount = scheduled_charge.amount;
+/// Accounts required for proposing new terms for a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ProposeChargeAmendment<'info> {
+    /// The party proposing the terms, the authority or the recipient
+    #[account(
+        constraint = proposer.key() == scheduled_charge.authority
+            || proposer.key() == scheduled_charge.recipient @ BlinkPayError::InvalidAuthority
+    )]
+    pub proposer: Signer<'info>,
 
-    // SECURITY: Update state BEFORE transfer (Checks-Effects-Interactions pattern)
-    // This prevents reentrancy attacks
-    scheduled_charge.last_executed_at = Some(current_time);
-    scheduled_charge.execution_count = scheduled_charge.execution_count.checked_add(1)
-        .ok_or(BlinkPayError::Overflow)?;
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused @ BlinkPayError::ScheduledChargeNotPending,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
 
-    // Handle recurring charges - calculate next execution time
-    match scheduled_charge.charge_type {
-        ScheduledChargeType::OneTime => {
-            scheduled_charge.status = ScheduledChargeStatus::Executed;
-        }
-        ScheduledChargeType::Recurring => {
-            // Calculate next execution time
-            if let Some(interval) = scheduled_charge.interval_seconds {
-                scheduled_charge.execute_at = current_time.checked_add(interval as i64)
-                    .ok_or(BlinkPayError::Overflow)?;
+    /// Authority's token account whose allowance grows with the new terms (for delegated SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
 
-                // Check if we've reached max executions after increment
-                if let Some(max_exec) = scheduled_charge.max_executions {
-                    if scheduled_charge.execution_count >= max_exec {
-                        scheduled_charge.status = ScheduledChargeStatus::Executed;
-                    }
-                }
-            } else {
-                // Recurring charge without interval should not exist, but handle gracefully
-                scheduled_charge.status = ScheduledChargeStatus::Executed;
-            }
-        }
-    }
+    /// Program delegate approved for the charge (for delegated SPL token charges)
+    #[account(
+        seeds = [b"charge_delegate", scheduled_charge.authority.as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
 
-    // SECURITY: Perform transfer AFTER state updates (Checks-Effects-Interactions)
-    if is_sol_token(&scheduled_charge.token_mint) {
-        // SOL payment
-        let authority = ctx.accounts.authority.as_ref()
-            .ok_or(BlinkPayError::InvalidAuthority)?;
-        let recipient = ctx.accounts.recipient.as_ref()
-            .ok_or(BlinkPayError::InvalidRecipient)?;
+    /// Token program (for delegated SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+}
 
-        transfer_sol(authority, recipient, amount, &ctx.accounts.system_program.to_account_info())?;
-        msg!("SOL scheduled charge executed: {} lamports to {}", amount, scheduled_charge.recipient);
-    } else {
-        // SPL token payment
-        let authority_token_account = ctx.accounts.authority_token_account.as_ref()
-            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
-        let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
-            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
-        let token_program = ctx.accounts.token_program.as_ref()
-            .ok_or(BlinkPayError::InvalidTokenMint)?;
+/// Accounts required for accepting proposed terms for a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptChargeAmendment<'info> {
+    /// The counterparty of the proposal, the authority or the recipient
+    #[account(
+        constraint = party.key() == scheduled_charge.authority
+            || party.key() == scheduled_charge.recipient @ BlinkPayError::InvalidAuthority
+    )]
+    pub party: Signer<'info>,
 
-        // Validate token account ownership
-        validate_token_account_ownership(authority_token_account, &scheduled_charge.authority)?;
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused @ BlinkPayError::ScheduledChargeNotPending,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
 
-        // Transfer tokens
-        transfer_spl_tokens(
-            authority_token_account,
-            recipient_token_account,
-            ctx.accounts.authority.as_ref().unwrap(),
-            &token_program.to_account_info(),
-            amount,
-        )?;
-        msg!("SPL token scheduled charge executed: {} tokens to {}", amount, scheduled_charge.recipient);
-    }
+    /// Authority's token account whose allowance grows with the new terms (for delegated SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
 
-    Ok(())
+    /// Program delegate approved for the charge (for delegated SPL token charges)
+    #[account(
+        seeds = [b"charge_delegate", scheduled_charge.authority.as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Token program (for delegated SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
 }
 
-/// Cancel a scheduled charge
-/// Only the authority can cancel their own charges
-pub fn cancel_scheduled_charge(ctx: Context<CancelScheduledCharge>) -> Result<()> {
-    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+/// Accounts required for rejecting or withdrawing proposed terms for a scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RejectChargeAmendment<'info> {
+    /// Either party, the counterparty rejects and the proposer withdraws
+    #[account(
+        constraint = party.key() == scheduled_charge.authority
+            || party.key() == scheduled_charge.recipient @ BlinkPayError::InvalidAuthority
+    )]
+    pub party: Signer<'info>,
 
-    // Mark as cancelled
-    scheduled_charge.status = ScheduledChargeStatus::Cancelled;
+    /// The scheduled charge account
+    #[account(mut)]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
 
-    msg!("Scheduled charge cancelled by authority");
+    /// Authority's token account whose allowance shrinks back to the current terms (for delegated SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
 
-    Ok(())
+    /// Program delegate approved for the charge (for delegated SPL token charges)
+    #[account(
+        seeds = [b"charge_delegate", scheduled_charge.authority.as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Token program (for delegated SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+/// Accounts required for adding funds to a scheduled charge vault
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct TopUpScheduledCharge<'info> {
+    /// The authority funding the charge (must be the creator)
+    #[account(
+        mut,
+        constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The scheduled charge account
+    #[account(
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused
+            || scheduled_charge.status == ScheduledChargeStatus::PastDue @ BlinkPayError::ScheduledChargeNotPending,
+        constraint = scheduled_charge.funding_mode == ChargeFundingMode::Vault @ BlinkPayError::ChargeNotVaultFunded,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+
+    /// Vault receiving the funds
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: SystemAccount<'info>,
+
+    /// Authority's token account (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Vault's token account (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = vault_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidChargeVault
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL charges)
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for withdrawing uncommitted funds from a scheduled charge vault
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct WithdrawUnused<'info> {
+    /// The authority withdrawing the funds (must be the creator)
+    #[account(
+        mut,
+        constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The scheduled charge account
+    #[account(
+        constraint = scheduled_charge.funding_mode == ChargeFundingMode::Vault @ BlinkPayError::ChargeNotVaultFunded,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+
+    /// Vault the funds are withdrawn from
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: SystemAccount<'info>,
+
+    /// Authority's token account receiving the funds (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Vault's token account (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = vault_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidChargeVault
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL charges)
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for reporting usage against a metered scheduled charge
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ReportUsage<'info> {
+    /// The party reporting usage, the recipient or the meter authority
+    pub reporter: Signer<'info>,
+
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.status == ScheduledChargeStatus::Pending
+            || scheduled_charge.status == ScheduledChargeStatus::Paused
+            || scheduled_charge.status == ScheduledChargeStatus::PastDue @ BlinkPayError::ScheduledChargeNotPending,
+        constraint = scheduled_charge.meter.is_some() @ BlinkPayError::ChargeNotMetered,
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+}
+
+/// Accounts required for closing a scheduled charge past its end date
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseExpiredCharge<'info> {
+    /// The party closing the charge (can be anyone)
+    pub closer: Signer<'info>,
+
+    /// The authority who funded the charge, receives the rent and any vault refund
+    #[account(
+        mut,
+        constraint = authority.key() == scheduled_charge.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: SystemAccount<'info>,
+
+    /// The scheduled charge account
+    #[account(
+        mut,
+        constraint = scheduled_charge.end_at.is_some() @ BlinkPayError::ScheduledChargeNotExpired,
+        close = authority
+    )]
+    pub scheduled_charge: Account<'info, ScheduledCharge>,
+
+    /// Authority's token account whose allowance is reduced or that receives the vault refund (for SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint)
+    )]
+    pub authority_token_account: Option<AccountInfo<'info>>,
+
+    /// Program delegate approved for the charge (for delegated SPL token charges closed by the authority)
+    #[account(
+        seeds = [b"charge_delegate", authority.key().as_ref()],
+        bump
+    )]
+    pub charge_delegate: Option<SystemAccount<'info>>,
+
+    /// Vault whose remaining balance is refunded (for vault-funded charges)
+    #[account(
+        mut,
+        seeds = [b"charge_vault", scheduled_charge.key().as_ref()],
+        bump
+    )]
+    pub charge_vault: Option<SystemAccount<'info>>,
+
+    /// Vault's token account, closed after the refund (for vault-funded SPL token charges)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&scheduled_charge.token_mint),
+        constraint = vault_token_account.key() == scheduled_charge.funding_token_account @ BlinkPayError::InvalidChargeVault
+    )]
+    pub vault_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token charges)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL vault refunds)
+    pub system_program: Program<'info, System>,
+}
+
+/// Create a new scheduled charge
+/// Sets up automatic payments that execute at specified times
+pub fn create_scheduled_charge(
+    ctx: Context<CreateScheduledCharge>,
+    params: ScheduledChargeParams,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let ScheduledChargeParams {
+        amount,
+        token_mint,
+        recipient,
+        execute_at,
+        charge_type: charge_type_u8,
+        recurrence,
+        max_executions,
+        memo,
+        vault_deposit,
+        keeper_tip,
+        catch_up_policy,
+        end_at,
+        trial_seconds,
+        intro_offer,
+        grace_period,
+        unit_price,
+        meter_authority,
+    } = params;
+
+    // SECURITY: Convert u8 to ScheduledChargeType with bounds checking
+    let charge_type = match charge_type_u8 {
+        0 => ScheduledChargeType::OneTime,
+        1 => ScheduledChargeType::Recurring,
+        2 => ScheduledChargeType::Metered,
+        _ => return err!(BlinkPayError::InvalidTimestamp),
+    };
+
+    // SECURITY: Comprehensive input validation
+    validate_scheduled_charge_params(
+        amount,
+        execute_at,
+        max_executions,
+        &charge_type,
+        recurrence.as_ref(),
+        current_time,
+    )?;
+    validate_introductory_terms(amount, &charge_type, trial_seconds, intro_offer.as_ref())?;
+    validate_meter_terms(&charge_type, unit_price, meter_authority.as_ref())?;
+    validate_token_mint(&token_mint)?;
+    validate_memo(&memo)?;
+    validate_recipient_not_authority(&recipient, ctx.accounts.authority.key)?;
+    if let Some(end_at) = end_at {
+        // SECURITY: The first execution must fall within the term
+        if end_at < execute_at {
+            return err!(BlinkPayError::InvalidTimestamp);
+        }
+    }
+    if let Some(deposit) = vault_deposit {
+        validate_amount(deposit)?;
+    }
+    if let Some(tip) = keeper_tip {
+        validate_amount(tip)?;
+    }
+    let keeper_tip = keeper_tip.unwrap_or(0);
+    let grace_period = grace_period.unwrap_or(DEFAULT_GRACE_PERIOD_SECONDS);
+
+    // Metered charges bill reported usage, `amount` caps what a single period can bill
+    let meter = unit_price.map(|unit_price| Meter {
+        unit_price,
+        meter_authority: meter_authority.unwrap_or(recipient),
+        usage: 0,
+    });
+    if grace_period > MAX_INTERVAL_SECONDS {
+        return err!(BlinkPayError::InvalidTimestamp);
+    }
+
+    // The trial runs from the first scheduled execution
+    let trial_ends_at = match trial_seconds {
+        Some(trial_seconds) => Some(execute_at.checked_add(trial_seconds as i64)
+            .ok_or(BlinkPayError::Overflow)?),
+        None => None,
+    };
+
+    // Every execution pays the recipient and, when set, tips the keeper
+    let execution_cost = safe_add(amount, keeper_tip)?;
+
+    // A deposit commits funds up front, otherwise each execution pulls from the authority
+    let funding_mode = if vault_deposit.is_some() {
+        ChargeFundingMode::Vault
+    } else {
+        ChargeFundingMode::Direct
+    };
+
+    // Advance the authority's counter so the next account gets a fresh index
+    let user_profile = &mut ctx.accounts.user_profile;
+    let index = user_profile.scheduled_charge_count;
+    user_profile.scheduled_charge_count = index.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    // SECURITY: Executions only ever pay from the token account chosen here
+    let funding_token_account = if funding_mode == ChargeFundingMode::Vault {
+        ctx.accounts.vault_token_account.as_ref()
+    } else {
+        ctx.accounts.authority_token_account.as_ref()
+    }
+    .map_or(Pubkey::default(), |token_account| token_account.key());
+
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+
+    // Initialize the scheduled charge
+    scheduled_charge.authority = *ctx.accounts.authority.key;
+    scheduled_charge.index = index;
+    scheduled_charge.recipient = recipient;
+    scheduled_charge.plan = Pubkey::default();
+    scheduled_charge.amount = amount;
+    scheduled_charge.keeper_tip = keeper_tip;
+    scheduled_charge.trial_ends_at = trial_ends_at;
+    scheduled_charge.intro_offer = intro_offer.clone();
+    scheduled_charge.meter = meter.clone();
+    scheduled_charge.proration_adjustment = 0;
+    scheduled_charge.token_mint = token_mint;
+    scheduled_charge.charge_type = charge_type;
+    scheduled_charge.funding_mode = funding_mode;
+    scheduled_charge.funding_token_account = funding_token_account;
+    scheduled_charge.delegated_allowance = 0;
+    scheduled_charge.execute_at = execute_at;
+    scheduled_charge.recurrence = recurrence.clone();
+    scheduled_charge.catch_up_policy = catch_up_policy.unwrap_or(CatchUpPolicy::SkipMissed);
+    scheduled_charge.pending_amendment = None;
+    scheduled_charge.auto_resume_at = None;
+    scheduled_charge.last_executed_at = None;
+    scheduled_charge.max_executions = max_executions;
+    scheduled_charge.end_at = end_at;
+    scheduled_charge.grace_period = grace_period;
+    scheduled_charge.failed_attempts = 0;
+    scheduled_charge.last_failure_at = None;
+    scheduled_charge.execution_count = 0;
+    scheduled_charge.memo = memo;
+    scheduled_charge.created_at = current_time;
+    scheduled_charge.status = ScheduledChargeStatus::Pending;
+    scheduled_charge.bump = ctx.bumps.scheduled_charge;
+
+    // Keepers pull delegated charges without the authority signing, so approve every execution now
+    let allowance = required_allowance(scheduled_charge);
+    sync_delegate_allowance(
+        scheduled_charge,
+        allowance,
+        &ctx.accounts.authority.to_account_info(),
+        ctx.accounts.authority_token_account.as_ref(),
+        ctx.accounts.charge_delegate.as_ref(),
+        ctx.accounts.token_program.as_ref(),
+    )?;
+
+    if let Some(deposit) = vault_deposit {
+        let charge_vault = ctx.accounts.charge_vault.as_ref()
+            .ok_or(BlinkPayError::InvalidChargeVault)?
+            .to_account_info();
+        let vault_token_account = ctx.accounts.vault_token_account.as_ref();
+
+        deposit_to_vault(
+            &token_mint,
+            &ctx.accounts.authority.to_account_info(),
+            &charge_vault,
+            ctx.accounts.authority_token_account.as_ref(),
+            vault_token_account,
+            ctx.accounts.token_program.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+            deposit,
+        )?;
+
+        // SECURITY: The vault must cover at least the first execution
+        if vault_available(&token_mint, &charge_vault, vault_token_account)? < execution_cost {
+            return err!(BlinkPayError::InsufficientFunds);
+        }
+
+        msg!("Charge vault funded with {} lamports/tokens", deposit);
+    }
+
+    msg!("Scheduled charge created: {} lamports/tokens to {} at timestamp {}", amount, recipient, execute_at);
+
+    emit_event!(ctx, ScheduledChargeCreated {
+        scheduled_charge: scheduled_charge.key(),
+        index,
+        authority: scheduled_charge.authority,
+        recipient,
+        token_mint,
+        amount,
+        charge_type: scheduled_charge.charge_type.clone(),
+        funding_mode: scheduled_charge.funding_mode.clone(),
+        vault_deposit,
+        keeper_tip,
+        trial_ends_at,
+        intro_offer,
+        meter,
+        execute_at,
+        recurrence,
+        catch_up_policy: scheduled_charge.catch_up_policy.clone(),
+        max_executions,
+        end_at,
+        grace_period,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Execute a scheduled charge
+/// Can be called by anyone when the execution time has been reached
+pub fn execute_scheduled_charge(ctx: Context<ExecuteScheduledCharge>) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let clock = Clock::get()?;
+    let current_time = clock.unix_timestamp;
+
+    // Charges with no slot left before their end date stop for good instead of executing
+    if has_expired(scheduled_charge, current_time)? {
+        scheduled_charge.status = ScheduledChargeStatus::Expired;
+
+        msg!("Scheduled charge expired, no further executions");
+
+        let expired = ScheduledChargeExpired {
+            scheduled_charge: scheduled_charge.key(),
+            authority: scheduled_charge.authority,
+            recipient: scheduled_charge.recipient,
+            end_at: scheduled_charge.end_at.unwrap_or(current_time),
+            execution_count: scheduled_charge.execution_count,
+            timestamp: current_time,
+        };
+        emit_event!(ctx, expired);
+
+        return Ok(());
+    }
+
+    // Paused charges resume on their own once the auto-resume time has passed
+    let mut resumed_at_slot = None;
+    if scheduled_charge.status == ScheduledChargeStatus::Paused {
+        match scheduled_charge.auto_resume_at {
+            Some(auto_resume_at) if current_time >= auto_resume_at => {
+                resume_charge(scheduled_charge, auto_resume_at)?;
+                resumed_at_slot = Some(scheduled_charge.execute_at);
+            }
+            _ => return err!(BlinkPayError::ScheduledChargePaused),
+        }
+    }
+
+    // SECURITY: Restore time validation with buffer for clock skew
+    if current_time < scheduled_charge.execute_at.saturating_sub(300) { // 5 minute buffer
+        return err!(BlinkPayError::ExecutionTimeNotReached);
+    }
+
+    // Check if already executed or cancelled
+    if scheduled_charge.status != ScheduledChargeStatus::Pending
+        && scheduled_charge.status != ScheduledChargeStatus::PastDue
+    {
+        return err!(BlinkPayError::ScheduledChargeNotPending);
+    }
+
+    // Past-due charges are retried on a backoff until the grace period after the missed payment runs out
+    let grace_ends_at = scheduled_charge.execute_at.saturating_add(scheduled_charge.grace_period as i64);
+    if scheduled_charge.status == ScheduledChargeStatus::PastDue {
+        if current_time > grace_ends_at {
+            scheduled_charge.status = ScheduledChargeStatus::Suspended;
+
+            msg!("Scheduled charge suspended after {} failed attempts", scheduled_charge.failed_attempts);
+
+            let suspended = ScheduledChargeSuspended {
+                scheduled_charge: scheduled_charge.key(),
+                authority: scheduled_charge.authority,
+                recipient: scheduled_charge.recipient,
+                failed_attempts: scheduled_charge.failed_attempts,
+                timestamp: current_time,
+            };
+            emit_event!(ctx, suspended);
+
+            return Ok(());
+        }
+
+        let last_failure_at = scheduled_charge.last_failure_at.unwrap_or(scheduled_charge.execute_at);
+        if current_time < dunning_retry_at(last_failure_at, scheduled_charge.failed_attempts) {
+            return err!(BlinkPayError::DunningRetryNotReady);
+        }
+    }
+
+    // Check max executions for recurring charges
+    if let Some(max_exec) = scheduled_charge.max_executions {
+        if scheduled_charge.execution_count >= max_exec {
+            return err!(BlinkPayError::MaxExecutionsExceeded);
+        }
+    }
+
+    // Executions due during the trial are free, then the introductory price applies before the regular amount
+    let in_trial = scheduled_charge.trial_ends_at
+        .is_some_and(|trial_ends_at| scheduled_charge.execute_at < trial_ends_at);
+    let intro_amount = scheduled_charge.intro_offer.as_ref()
+        .filter(|offer| offer.executions > 0)
+        .map(|offer| offer.amount);
+    // Metered charges bill the usage reported during the period, up to the payer's cap
+    let usage = scheduled_charge.meter.as_ref().map_or(0, |meter| meter.usage);
+    let amount = match (&scheduled_charge.meter, intro_amount) {
+        _ if in_trial => 0,
+        (Some(meter), _) => meter.usage.saturating_mul(meter.unit_price).min(scheduled_charge.amount),
+        (None, Some(intro_amount)) => intro_amount,
+        (None, None) => scheduled_charge.amount,
+    };
+
+    // Proration carried from a plan change is settled on the next billed execution
+    let (amount, proration_carry) = if amount == 0 {
+        (0, scheduled_charge.proration_adjustment)
+    } else {
+        apply_proration(amount, scheduled_charge.proration_adjustment)
+    };
+    let proration = scheduled_charge.proration_adjustment - proration_carry;
+
+    // Nothing is pulled from the authority for free executions, so no tip is paid either
+    // Trial runs only advance the schedule, and the allowance and vault are sized for billed runs
+    let keeper_tip = if amount == 0 { 0 } else { scheduled_charge.keeper_tip };
+
+    // Keepers are tipped in the charge's own token
+    let tip_destination = if keeper_tip == 0 {
+        None
+    } else if is_sol_token(&scheduled_charge.token_mint) {
+        Some(ctx.accounts.executor.to_account_info())
+    } else {
+        let executor_token_account = ctx.accounts.executor_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        validate_token_account_ownership(executor_token_account, ctx.accounts.executor.key)?;
+        Some(executor_token_account.clone())
+    };
+
+    // Check the funds up front so a shortfall is recorded instead of reverting the transaction
+    let execution_cost = safe_add(amount, keeper_tip)?;
+    let funds_available = if amount == 0 {
+        execution_cost
+    } else if scheduled_charge.funding_mode == ChargeFundingMode::Vault {
+        let charge_vault = ctx.accounts.charge_vault.as_ref()
+            .ok_or(BlinkPayError::InvalidChargeVault)?;
+        vault_available(
+            &scheduled_charge.token_mint,
+            &charge_vault.to_account_info(),
+            ctx.accounts.vault_token_account.as_ref(),
+        )?
+    } else if is_sol_token(&scheduled_charge.token_mint) {
+        // The authority's account has to stay rent exempt after paying
+        ctx.accounts.authority.as_ref()
+            .ok_or(BlinkPayError::InvalidAuthority)?
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0))
+    } else {
+        let authority_token_account = ctx.accounts.authority_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        let charge_delegate = ctx.accounts.charge_delegate.as_ref()
+            .ok_or(BlinkPayError::InvalidChargeDelegate)?;
+
+        // SECURITY: Only the account approved at creation may mark the charge past due, and only while the authority still owns it
+        validate_token_account_ownership(authority_token_account, &scheduled_charge.authority)?;
+        validate_token_account_mint(authority_token_account, &scheduled_charge.token_mint)?;
+
+        token_account_balance(authority_token_account)?
+            .min(delegated_allowance(authority_token_account, &charge_delegate.key())?)
+    };
+
+    if funds_available < execution_cost {
+        scheduled_charge.status = ScheduledChargeStatus::PastDue;
+        scheduled_charge.failed_attempts = scheduled_charge.failed_attempts.checked_add(1)
+            .ok_or(BlinkPayError::Overflow)?;
+        scheduled_charge.last_failure_at = Some(current_time);
+
+        msg!("Scheduled charge past due: {} available, {} needed", funds_available, execution_cost);
+
+        let past_due = ScheduledChargePastDue {
+            scheduled_charge: scheduled_charge.key(),
+            authority: scheduled_charge.authority,
+            recipient: scheduled_charge.recipient,
+            amount_due: execution_cost,
+            failed_attempts: scheduled_charge.failed_attempts,
+            next_retry_at: dunning_retry_at(current_time, scheduled_charge.failed_attempts),
+            grace_ends_at,
+            timestamp: current_time,
+        };
+        if let Some(next_execute_at) = resumed_at_slot {
+            emit_event!(ctx, ScheduledChargeResumed {
+                scheduled_charge: past_due.scheduled_charge,
+                authority: past_due.authority,
+                resumed_by: ctx.accounts.executor.key(),
+                next_execute_at,
+                timestamp: current_time,
+            });
+        }
+        emit_event!(ctx, past_due);
+
+        return Ok(());
+    }
+
+    // SECURITY: Update state BEFORE transfer (Checks-Effects-Interactions pattern)
+    // This prevents reentrancy attacks
+    scheduled_charge.status = ScheduledChargeStatus::Pending;
+    scheduled_charge.failed_attempts = 0;
+    if let Some(offer) = scheduled_charge.intro_offer.as_mut().filter(|_| !in_trial) {
+        offer.executions = offer.executions.saturating_sub(1);
+    }
+    if let Some(meter) = scheduled_charge.meter.as_mut() {
+        meter.usage = 0;
+    }
+    scheduled_charge.proration_adjustment = proration_carry;
+    if scheduled_charge.funding_mode == ChargeFundingMode::Direct && !is_sol_token(&scheduled_charge.token_mint) {
+        // The token program lowers the delegate's allowance by what it pulls
+        scheduled_charge.delegated_allowance = scheduled_charge.delegated_allowance.saturating_sub(execution_cost);
+    }
+    scheduled_charge.last_executed_at = Some(current_time);
+    // Trial runs are free and do not use up any of the capped executions
+    if !in_trial {
+        scheduled_charge.execution_count = scheduled_charge.execution_count.checked_add(1)
+            .ok_or(BlinkPayError::Overflow)?;
+    }
+
+    // Handle recurring charges - calculate next execution time
+    let mut periods_skipped = 0;
+    match scheduled_charge.charge_type {
+        ScheduledChargeType::OneTime => {
+            scheduled_charge.status = ScheduledChargeStatus::Executed;
+        }
+        ScheduledChargeType::Recurring | ScheduledChargeType::Metered => {
+            if let Some(recurrence) = scheduled_charge.recurrence.clone() {
+                // Periods that came due after this one are dropped according to the catch-up policy
+                periods_skipped = match scheduled_charge.catch_up_policy {
+                    CatchUpPolicy::ExecuteAll => 0,
+                    CatchUpPolicy::SkipMissed => {
+                        periods_between(&recurrence, scheduled_charge.execute_at, current_time)?
+                    }
+                    CatchUpPolicy::Capped { max_periods } => {
+                        periods_between(&recurrence, scheduled_charge.execute_at, current_time)?
+                            .saturating_sub(max_periods as u64)
+                    }
+                };
+                let period = advance_periods(&recurrence, scheduled_charge.execute_at, periods_skipped)?;
+
+                // Calculate next execution time from the billing anchor so late executions never drift
+                scheduled_charge.execute_at = next_occurrence(&recurrence, period)?;
+
+                // Check if we've reached max executions after increment
+                if let Some(max_exec) = scheduled_charge.max_executions {
+                    if scheduled_charge.execution_count >= max_exec {
+                        scheduled_charge.status = ScheduledChargeStatus::Executed;
+                    }
+                }
+
+                // The next slot falls after the end date, so this was the final execution
+                if scheduled_charge.status == ScheduledChargeStatus::Pending
+                    && scheduled_charge.end_at.is_some_and(|end_at| scheduled_charge.execute_at > end_at)
+                {
+                    scheduled_charge.status = ScheduledChargeStatus::Expired;
+                }
+            } else {
+                // Recurring charge without recurrence should not exist, but handle gracefully
+                scheduled_charge.status = ScheduledChargeStatus::Executed;
+            }
+        }
+    }
+
+    // SECURITY: Perform transfer AFTER state updates (Checks-Effects-Interactions)
+    if amount == 0 {
+        msg!("Scheduled charge executed with nothing to bill, nothing transferred");
+    } else if scheduled_charge.funding_mode == ChargeFundingMode::Vault {
+        // Vault payment, the funds were committed up front
+        let charge_vault = ctx.accounts.charge_vault.as_ref()
+            .ok_or(BlinkPayError::InvalidChargeVault)?
+            .to_account_info();
+        let vault_bump = ctx.bumps.charge_vault
+            .ok_or(BlinkPayError::InvalidChargeVault)?;
+        let vault_token_account = ctx.accounts.vault_token_account.as_ref();
+
+        let destination = if is_sol_token(&scheduled_charge.token_mint) {
+            ctx.accounts.recipient.as_ref()
+                .ok_or(BlinkPayError::InvalidRecipient)?
+        } else {
+            let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+                .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+            validate_token_account_ownership(recipient_token_account, &scheduled_charge.recipient)?;
+            recipient_token_account
+        };
+
+        withdraw_from_vault(
+            &scheduled_charge.token_mint,
+            &scheduled_charge.key(),
+            &charge_vault,
+            vault_bump,
+            vault_token_account,
+            destination,
+            ctx.accounts.token_program.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+
+        if let Some(tip_destination) = &tip_destination {
+            withdraw_from_vault(
+                &scheduled_charge.token_mint,
+                &scheduled_charge.key(),
+                &charge_vault,
+                vault_bump,
+                vault_token_account,
+                tip_destination,
+                ctx.accounts.token_program.as_ref(),
+                &ctx.accounts.system_program.to_account_info(),
+                keeper_tip,
+            )?;
+        }
+        msg!("Scheduled charge executed from vault: {} lamports/tokens to {}", amount, scheduled_charge.recipient);
+    } else if is_sol_token(&scheduled_charge.token_mint) {
+        // SOL payment
+        let authority = ctx.accounts.authority.as_ref()
+            .ok_or(BlinkPayError::InvalidAuthority)?;
+        let recipient = ctx.accounts.recipient.as_ref()
+            .ok_or(BlinkPayError::InvalidRecipient)?;
+
+        transfer_sol(authority, recipient, amount, &ctx.accounts.system_program.to_account_info())?;
+        if let Some(tip_destination) = &tip_destination {
+            transfer_sol(authority, tip_destination, keeper_tip, &ctx.accounts.system_program.to_account_info())?;
+        }
+        msg!("SOL scheduled charge executed: {} lamports to {}", amount, scheduled_charge.recipient);
+    } else {
+        // SPL token payment
+        let authority_token_account = ctx.accounts.authority_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+        let token_program = ctx.accounts.token_program.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+        let charge_delegate = ctx.accounts.charge_delegate.as_ref()
+            .ok_or(BlinkPayError::InvalidChargeDelegate)?;
+        let delegate_bump = ctx.bumps.charge_delegate
+            .ok_or(BlinkPayError::InvalidChargeDelegate)?;
+
+        // Validate token account ownership, the authority's account was checked with the funds
+        validate_token_account_ownership(recipient_token_account, &scheduled_charge.recipient)?;
+
+        let authority_key = scheduled_charge.authority;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"charge_delegate",
+            authority_key.as_ref(),
+            &[delegate_bump],
+        ]];
+
+        // Pull tokens through the delegate so the authority does not need to sign
+        transfer_spl_tokens_signed(
+            authority_token_account,
+            recipient_token_account,
+            &charge_delegate.to_account_info(),
+            &token_program.to_account_info(),
+            amount,
+            signer_seeds,
+        )?;
+        if let Some(tip_destination) = &tip_destination {
+            transfer_spl_tokens_signed(
+                authority_token_account,
+                tip_destination,
+                &charge_delegate.to_account_info(),
+                &token_program.to_account_info(),
+                keeper_tip,
+                signer_seeds,
+            )?;
+        }
+        msg!("SPL token scheduled charge executed: {} tokens to {}", amount, scheduled_charge.recipient);
+    }
+
+    let still_pending = scheduled_charge.status == ScheduledChargeStatus::Pending;
+    let executed = ScheduledChargeExecuted {
+        scheduled_charge: scheduled_charge.key(),
+        executor: ctx.accounts.executor.key(),
+        authority: scheduled_charge.authority,
+        recipient: scheduled_charge.recipient,
+        token_mint: scheduled_charge.token_mint,
+        amount,
+        keeper_tip,
+        usage,
+        proration,
+        periods_skipped,
+        execution_count: scheduled_charge.execution_count,
+        next_execute_at: still_pending.then_some(scheduled_charge.execute_at),
+        status: scheduled_charge.status.clone(),
+        timestamp: current_time,
+    };
+
+    if let Some(next_execute_at) = resumed_at_slot {
+        emit_event!(ctx, ScheduledChargeResumed {
+            scheduled_charge: executed.scheduled_charge,
+            authority: executed.authority,
+            resumed_by: executed.executor,
+            next_execute_at,
+            timestamp: current_time,
+        });
+    }
+    emit_event!(ctx, executed);
+
+    Ok(())
+}
+
+/// Cancel a scheduled charge
+/// The authority or the recipient can cancel, the rent always returns to the authority
+pub fn cancel_scheduled_charge(
+    ctx: Context<CancelScheduledCharge>,
+    reason: CancellationReason,
+) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let cancelled_by = ctx.accounts.canceller.key();
+
+    // Mark as cancelled
+    scheduled_charge.status = ScheduledChargeStatus::Cancelled;
+
+    let mut vault_refund = 0;
+    if scheduled_charge.funding_mode == ChargeFundingMode::Vault {
+        vault_refund = refund_vault(
+            &scheduled_charge.token_mint,
+            &scheduled_charge.key(),
+            ctx.accounts.charge_vault.as_ref(),
+            ctx.bumps.charge_vault,
+            ctx.accounts.vault_token_account.as_ref(),
+            &ctx.accounts.authority,
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+    } else if cancelled_by == scheduled_charge.authority {
+        // Only the token account owner can lower the allowance, so this is skipped when the recipient cancels
+        sync_delegate_allowance(
+            scheduled_charge,
+            0,
+            &ctx.accounts.canceller.to_account_info(),
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.charge_delegate.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+    }
+
+    // Left on the shared delegate when the recipient cancels, until the authority lowers it
+    let unreleased_allowance = scheduled_charge.delegated_allowance;
+    if unreleased_allowance > 0 {
+        msg!("Charge delegate still holds {} tokens of allowance for this charge", unreleased_allowance);
+    }
+
+    msg!("Scheduled charge cancelled by {}", cancelled_by);
+
+    emit_event!(ctx, ScheduledChargeCancelled {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        recipient: scheduled_charge.recipient,
+        cancelled_by,
+        reason,
+        execution_count: scheduled_charge.execution_count,
+        vault_refund,
+        unreleased_allowance,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Pause a scheduled charge
+/// Only the authority can pause, optionally resuming automatically at a set time
+pub fn pause_scheduled_charge(
+    ctx: Context<PauseScheduledCharge>,
+    auto_resume_at: Option<i64>,
+) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    if let Some(auto_resume_at) = auto_resume_at {
+        validate_expiry(auto_resume_at, current_time)?;
+    }
+
+    scheduled_charge.status = ScheduledChargeStatus::Paused;
+    scheduled_charge.auto_resume_at = auto_resume_at;
+
+    msg!("Scheduled charge paused by authority");
+
+    emit_event!(ctx, ScheduledChargePaused {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        auto_resume_at,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Resume a paused scheduled charge
+/// Reschedules the charge to its next slot without charging for the paused periods
+pub fn resume_scheduled_charge(ctx: Context<ResumeScheduledCharge>) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    resume_charge(scheduled_charge, current_time)?;
+
+    msg!("Scheduled charge resumed, next execution at {}", scheduled_charge.execute_at);
+
+    emit_event!(ctx, ScheduledChargeResumed {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        resumed_by: ctx.accounts.authority.key(),
+        next_execute_at: scheduled_charge.execute_at,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Propose new terms for a scheduled charge
+/// Either party may propose, the terms only take effect once the other party accepts
+pub fn propose_charge_amendment(
+    ctx: Context<ProposeChargeAmendment>,
+    amount: u64,
+    recurrence: Option<Recurrence>,
+    max_executions: Option<u32>,
+    recipient: Pubkey,
+) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let proposer = ctx.accounts.proposer.key();
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // SECURITY: New terms go through the same validation as on creation
+    validate_charge_terms(amount, max_executions, &scheduled_charge.charge_type, recurrence.as_ref())?;
+    validate_recipient_not_authority(&recipient, &scheduled_charge.authority)?;
+    validate_max_executions_remaining(scheduled_charge, max_executions)?;
+
+    // SECURITY: A plan subscription always pays the plan's merchant
+    if scheduled_charge.plan != Pubkey::default() && recipient != scheduled_charge.recipient {
+        return err!(BlinkPayError::InvalidPlanChange);
+    }
+
+    let amendment = ChargeAmendment {
+        proposed_by: proposer,
+        amount,
+        recurrence,
+        max_executions,
+        recipient,
+        proposed_at: current_time,
+    };
+
+    // A new proposal replaces any pending one
+    scheduled_charge.pending_amendment = Some(amendment.clone());
+
+    // The authority consents to its own proposal, so cover the new terms now
+    if proposer == scheduled_charge.authority {
+        let allowance = required_allowance(scheduled_charge);
+        sync_delegate_allowance(
+            scheduled_charge,
+            allowance,
+            &ctx.accounts.proposer.to_account_info(),
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.charge_delegate.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+    }
+
+    msg!("Scheduled charge amendment proposed by {}", proposer);
+
+    emit_event!(ctx, ScheduledChargeAmendmentProposed {
+        scheduled_charge: scheduled_charge.key(),
+        proposed_by: proposer,
+        amount: amendment.amount,
+        recurrence: amendment.recurrence,
+        max_executions: amendment.max_executions,
+        recipient: amendment.recipient,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Accept proposed terms for a scheduled charge
+/// Only the counterparty of the proposal can accept, the terms apply from the next execution
+pub fn accept_charge_amendment(
+    ctx: Context<AcceptChargeAmendment>,
+    amount: u64,
+    recurrence: Option<Recurrence>,
+    max_executions: Option<u32>,
+    recipient: Pubkey,
+) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let party = ctx.accounts.party.key();
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let amendment = scheduled_charge.pending_amendment.clone()
+        .ok_or(BlinkPayError::NoPendingAmendment)?;
+
+    // SECURITY: Only the other party can consent to the proposed terms
+    let counterparty = if amendment.proposed_by == scheduled_charge.authority {
+        scheduled_charge.recipient
+    } else {
+        scheduled_charge.authority
+    };
+    if party != counterparty {
+        return err!(BlinkPayError::InvalidAuthority);
+    }
+
+    // SECURITY: A proposal can be replaced at any time, so only the terms the party saw are accepted
+    if amendment.amount != amount
+        || amendment.recurrence != recurrence
+        || amendment.max_executions != max_executions
+        || amendment.recipient != recipient
+    {
+        return err!(BlinkPayError::AmendmentMismatch);
+    }
+
+    // Executions may have happened since the proposal
+    validate_max_executions_remaining(scheduled_charge, amendment.max_executions)?;
+
+    // Apply the new terms
+    scheduled_charge.amount = amendment.amount;
+    scheduled_charge.recurrence = amendment.recurrence.clone();
+    scheduled_charge.max_executions = amendment.max_executions;
+    scheduled_charge.recipient = amendment.recipient;
+    scheduled_charge.pending_amendment = None;
+
+    if party == scheduled_charge.authority {
+        let allowance = required_allowance(scheduled_charge);
+        sync_delegate_allowance(
+            scheduled_charge,
+            allowance,
+            &ctx.accounts.party.to_account_info(),
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.charge_delegate.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+    }
+
+    msg!("Scheduled charge amended: {} lamports/tokens to {}", amendment.amount, amendment.recipient);
+
+    emit_event!(ctx, ScheduledChargeAmended {
+        scheduled_charge: scheduled_charge.key(),
+        proposed_by: amendment.proposed_by,
+        accepted_by: party,
+        amount: amendment.amount,
+        recurrence: amendment.recurrence,
+        max_executions: amendment.max_executions,
+        recipient: amendment.recipient,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Reject proposed terms for a scheduled charge
+/// The counterparty rejects or the proposer withdraws, the current terms stay in place
+pub fn reject_charge_amendment(ctx: Context<RejectChargeAmendment>) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+
+    let amendment = scheduled_charge.pending_amendment.take()
+        .ok_or(BlinkPayError::NoPendingAmendment)?;
+
+    // Release what an authority proposal added, only the token account owner can lower the allowance
+    let allowance = required_allowance(scheduled_charge);
+    if ctx.accounts.party.key() == scheduled_charge.authority {
+        sync_delegate_allowance(
+            scheduled_charge,
+            allowance,
+            &ctx.accounts.party.to_account_info(),
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.charge_delegate.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+    }
+
+    // Left on the shared delegate when the recipient rejects, until the authority next syncs it
+    let unreleased_allowance = scheduled_charge.delegated_allowance.saturating_sub(allowance);
+    if unreleased_allowance > 0 {
+        msg!("Charge delegate still holds {} tokens of allowance beyond the current terms", unreleased_allowance);
+    }
+
+    msg!("Scheduled charge amendment rejected");
+
+    emit_event!(ctx, ScheduledChargeAmendmentRejected {
+        scheduled_charge: scheduled_charge.key(),
+        proposed_by: amendment.proposed_by,
+        rejected_by: ctx.accounts.party.key(),
+        unreleased_allowance,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Top up a vault-funded scheduled charge
+/// Adds funds to the charge vault for future executions
+pub fn top_up_scheduled_charge(ctx: Context<TopUpScheduledCharge>, amount: u64) -> Result<()> {
+    validate_amount(amount)?;
+
+    let scheduled_charge = &ctx.accounts.scheduled_charge;
+    let charge_vault = ctx.accounts.charge_vault.to_account_info();
+    let vault_token_account = ctx.accounts.vault_token_account.as_ref();
+
+    deposit_to_vault(
+        &scheduled_charge.token_mint,
+        &ctx.accounts.authority.to_account_info(),
+        &charge_vault,
+        ctx.accounts.authority_token_account.as_ref(),
+        vault_token_account,
+        ctx.accounts.token_program.as_ref(),
+        &ctx.accounts.system_program.to_account_info(),
+        amount,
+    )?;
+
+    let available_balance = vault_available(&scheduled_charge.token_mint, &charge_vault, vault_token_account)?;
+
+    msg!("Charge vault topped up: {} lamports/tokens, {} available", amount, available_balance);
+
+    emit_event!(ctx, ScheduledChargeToppedUp {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        amount,
+        available_balance,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Withdraw unused funds from a scheduled charge vault
+/// Only funds beyond what the next execution needs can be withdrawn
+pub fn withdraw_unused(ctx: Context<WithdrawUnused>) -> Result<()> {
+    let scheduled_charge = &ctx.accounts.scheduled_charge;
+    let charge_vault = ctx.accounts.charge_vault.to_account_info();
+    let vault_token_account = ctx.accounts.vault_token_account.as_ref();
+
+    // SECURITY: The executions still owed and any debit carried from a plan change stay committed
+    let committed = if scheduled_charge.status == ScheduledChargeStatus::Pending
+        || scheduled_charge.status == ScheduledChargeStatus::Paused
+        || scheduled_charge.status == ScheduledChargeStatus::PastDue
+    {
+        vault_committed(scheduled_charge)?
+    } else {
+        0
+    };
+    let available = vault_available(&scheduled_charge.token_mint, &charge_vault, vault_token_account)?;
+    let amount = available.saturating_sub(committed);
+    if amount == 0 {
+        return err!(BlinkPayError::InsufficientFunds);
+    }
+
+    let destination = if is_sol_token(&scheduled_charge.token_mint) {
+        ctx.accounts.authority.to_account_info()
+    } else {
+        let authority_token_account = ctx.accounts.authority_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        validate_token_account_ownership(authority_token_account, ctx.accounts.authority.key)?;
+        authority_token_account.clone()
+    };
+
+    withdraw_from_vault(
+        &scheduled_charge.token_mint,
+        &scheduled_charge.key(),
+        &charge_vault,
+        ctx.bumps.charge_vault,
+        vault_token_account,
+        &destination,
+        ctx.accounts.token_program.as_ref(),
+        &ctx.accounts.system_program.to_account_info(),
+        amount,
+    )?;
+
+    msg!("Unused vault funds withdrawn: {} lamports/tokens", amount);
+
+    emit_event!(ctx, ScheduledChargeVaultWithdrawn {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        amount,
+        available_balance: available - amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Report usage against a metered scheduled charge
+/// The recipient or the meter authority adds units billed on the next execution
+pub fn report_usage(ctx: Context<ReportUsage>, units: u64) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let reporter = ctx.accounts.reporter.key();
+    let recipient = scheduled_charge.recipient;
+
+    validate_amount(units)?;
+
+    let meter = scheduled_charge.meter.as_mut()
+        .ok_or(BlinkPayError::ChargeNotMetered)?;
+
+    // SECURITY: Only the merchant or the meter it designated may report usage
+    if reporter != recipient && reporter != meter.meter_authority {
+        return err!(BlinkPayError::InvalidAuthority);
+    }
+
+    meter.usage = meter.usage.checked_add(units)
+        .ok_or(BlinkPayError::Overflow)?;
+    let period_usage = meter.usage;
+
+    msg!("Usage reported: {} units, {} this period", units, period_usage);
+
+    emit_event!(ctx, ScheduledChargeUsageReported {
+        scheduled_charge: scheduled_charge.key(),
+        reporter,
+        units,
+        period_usage,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Close a scheduled charge that has passed its end date
+/// Anyone can close, the rent and any vault balance return to the authority
+pub fn close_expired_charge(ctx: Context<CloseExpiredCharge>) -> Result<()> {
+    let scheduled_charge = &mut ctx.accounts.scheduled_charge;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // SECURITY: Only charges that can never execute again may be closed by a third party
+    if scheduled_charge.status != ScheduledChargeStatus::Expired
+        && !has_expired(scheduled_charge, current_time)?
+    {
+        return err!(BlinkPayError::ScheduledChargeNotExpired);
+    }
+
+    let vault_refund = if scheduled_charge.funding_mode == ChargeFundingMode::Vault {
+        refund_vault(
+            &scheduled_charge.token_mint,
+            &scheduled_charge.key(),
+            ctx.accounts.charge_vault.as_ref(),
+            ctx.bumps.charge_vault,
+            ctx.accounts.vault_token_account.as_ref(),
+            &ctx.accounts.authority,
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?
+    } else {
+        0
+    };
+
+    // Only the token account owner can lower the allowance, so this is skipped when anyone else closes
+    if ctx.accounts.closer.key() == scheduled_charge.authority {
+        sync_delegate_allowance(
+            scheduled_charge,
+            0,
+            &ctx.accounts.closer.to_account_info(),
+            ctx.accounts.authority_token_account.as_ref(),
+            ctx.accounts.charge_delegate.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+    }
+
+    // Left on the shared delegate until the authority lowers it
+    let unreleased_allowance = scheduled_charge.delegated_allowance;
+    if unreleased_allowance > 0 {
+        msg!("Charge delegate still holds {} tokens of allowance for this charge", unreleased_allowance);
+    }
+
+    msg!("Expired scheduled charge closed by {}", ctx.accounts.closer.key());
+
+    emit_event!(ctx, ScheduledChargeClosed {
+        scheduled_charge: scheduled_charge.key(),
+        authority: scheduled_charge.authority,
+        closed_by: ctx.accounts.closer.key(),
+        vault_refund,
+        unreleased_allowance,
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Check that a new execution cap leaves at least one execution to run
+pub(crate) fn validate_max_executions_remaining(
+    scheduled_charge: &ScheduledCharge,
+    max_executions: Option<u32>,
+) -> Result<()> {
+    if max_executions.is_some_and(|max_exec| max_exec <= scheduled_charge.execution_count) {
+        return err!(BlinkPayError::MaxExecutionsExceeded);
+    }
+
+    Ok(())
+}
+
+/// Allowance a delegated charge needs for its remaining executions
+/// Covers the costlier of the current and pending terms plus any debit carried from a plan change
+pub(crate) fn required_allowance(scheduled_charge: &ScheduledCharge) -> u64 {
+    let remaining_cost = |amount: u64, max_executions: Option<u32>| scheduled_charge_allowance(
+        amount.saturating_add(scheduled_charge.keeper_tip),
+        &scheduled_charge.charge_type,
+        max_executions,
+        scheduled_charge.execution_count,
+    );
+
+    let current_cost = remaining_cost(scheduled_charge.amount, scheduled_charge.max_executions);
+    let amended_cost = scheduled_charge.pending_amendment.as_ref()
+        .map_or(0, |amendment| remaining_cost(amendment.amount, amendment.max_executions));
+
+    current_cost.max(amended_cost)
+        .saturating_add(scheduled_charge.proration_adjustment.max(0) as u64)
+}
+
+/// Set this charge's share of the charge delegate's allowance to `target`
+/// The delegate is shared by all of the authority's charges, so only this charge's share moves
+pub(crate) fn sync_delegate_allowance<'info>(
+    scheduled_charge: &mut ScheduledCharge,
+    target: u64,
+    authority: &AccountInfo<'info>,
+    authority_token_account: Option<&AccountInfo<'info>>,
+    charge_delegate: Option<&SystemAccount<'info>>,
+    token_program: Option<&Program<'info, Token>>,
+) -> Result<()> {
+    if scheduled_charge.funding_mode != ChargeFundingMode::Direct
+        || is_sol_token(&scheduled_charge.token_mint)
+        || scheduled_charge.delegated_allowance == target
+    {
+        return Ok(());
+    }
+
+    let authority_token_account = authority_token_account
+        .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+    let charge_delegate = charge_delegate
+        .ok_or(BlinkPayError::InvalidChargeDelegate)?;
+    let token_program = token_program
+        .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+    // SECURITY: Only delegate from the authority's own account for this mint, the one the charge pays from
+    if authority_token_account.key() != scheduled_charge.funding_token_account {
+        return err!(BlinkPayError::InvalidTokenAccountOwner);
+    }
+    validate_token_account_ownership(authority_token_account, authority.key)?;
+    validate_token_account_mint(authority_token_account, &scheduled_charge.token_mint)?;
+
+    let allowance = delegated_allowance(authority_token_account, &charge_delegate.key())?
+        .saturating_sub(scheduled_charge.delegated_allowance)
+        .saturating_add(target);
+
+    approve_spl_delegate(
+        authority_token_account,
+        &charge_delegate.to_account_info(),
+        authority,
+        &token_program.to_account_info(),
+        allowance,
+    )?;
+    scheduled_charge.delegated_allowance = target;
+
+    msg!("Charge delegate approved for {} tokens", allowance);
+
+    Ok(())
+}
+
+/// Move a paused charge back to pending at its first slot at or after `from`
+/// Periods that fell inside the pause are never charged
+fn resume_charge(scheduled_charge: &mut ScheduledCharge, from: i64) -> Result<()> {
+    scheduled_charge.execute_at = resumed_slot(scheduled_charge, from)?;
+    scheduled_charge.status = ScheduledChargeStatus::Pending;
+    scheduled_charge.auto_resume_at = None;
+
+    Ok(())
+}
+
+/// First slot of a paused charge at or after `from`
+fn resumed_slot(scheduled_charge: &ScheduledCharge, from: i64) -> Result<i64> {
+    match &scheduled_charge.recurrence {
+        Some(recurrence) => first_occurrence_from(recurrence, scheduled_charge.execute_at, from),
+        None => Ok(scheduled_charge.execute_at.max(from)),
+    }
+}
+
+/// Check whether a charge that has not finished can never execute again
+/// A slot due by the end date is still billed when executed late, only later slots expire the charge
+fn has_expired(scheduled_charge: &ScheduledCharge, now: i64) -> Result<bool> {
+    let Some(end_at) = scheduled_charge.end_at else {
+        return Ok(false);
+    };
+    let grace_ends_at = scheduled_charge.execute_at.saturating_add(scheduled_charge.grace_period as i64);
+
+    let next_slot = match scheduled_charge.status {
+        ScheduledChargeStatus::Pending => scheduled_charge.execute_at,
+        // Past-due slots are retried until their grace period runs out
+        ScheduledChargeStatus::PastDue if now <= grace_ends_at => scheduled_charge.execute_at,
+        ScheduledChargeStatus::PastDue | ScheduledChargeStatus::Suspended => return Ok(now > end_at),
+        // A paused charge resumes no earlier than its auto-resume time or now
+        ScheduledChargeStatus::Paused => {
+            let resume_from = scheduled_charge.auto_resume_at.map_or(now, |auto_resume_at| auto_resume_at.min(now));
+            resumed_slot(scheduled_charge, resume_from)?
+        }
+        _ => return Ok(false),
+    };
+
+    Ok(next_slot > end_at)
+}
+
+/// Vault funds a charge still owes its recipient and keeper
+/// Capped charges commit every remaining execution up to the end date, open-ended ones only the next
+fn vault_committed(scheduled_charge: &ScheduledCharge) -> Result<u64> {
+    let capped = scheduled_charge.charge_type == ScheduledChargeType::OneTime
+        || scheduled_charge.max_executions.is_some();
+    let mut executions = if capped {
+        scheduled_charge_allowance(
+            1,
+            &scheduled_charge.charge_type,
+            scheduled_charge.max_executions,
+            scheduled_charge.execution_count,
+        )
+    } else {
+        1
+    };
+
+    // Slots after the end date are never executed
+    if let (Some(end_at), Some(recurrence)) = (scheduled_charge.end_at, scheduled_charge.recurrence.as_ref()) {
+        let slots = if scheduled_charge.execute_at > end_at {
+            0
+        } else {
+            safe_add(1, periods_between(recurrence, scheduled_charge.execute_at, end_at)?)?
+        };
+        executions = executions.min(slots);
+    }
+
+    let execution_cost = scheduled_charge.amount.saturating_add(scheduled_charge.keeper_tip);
+    safe_add(
+        execution_cost.saturating_mul(executions),
+        scheduled_charge.proration_adjustment.max(0) as u64,
+    )
+}
+
+/// Refund everything left in a charge vault to the authority, including the SOL rent reserve
+/// SPL vault token accounts are closed afterwards and their rent returned to the authority
+#[allow(clippy::too_many_arguments)]
+fn refund_vault<'info>(
+    token_mint: &Pubkey,
+    scheduled_charge_key: &Pubkey,
+    charge_vault: Option<&SystemAccount<'info>>,
+    vault_bump: Option<u8>,
+    vault_token_account: Option<&AccountInfo<'info>>,
+    authority: &SystemAccount<'info>,
+    authority_token_account: Option<&AccountInfo<'info>>,
+    token_program: Option<&Program<'info, Token>>,
+    system_program: &AccountInfo<'info>,
+) -> Result<u64> {
+    let charge_vault = charge_vault
+        .ok_or(BlinkPayError::InvalidChargeVault)?
+        .to_account_info();
+    let vault_bump = vault_bump
+        .ok_or(BlinkPayError::InvalidChargeVault)?;
+
+    let (vault_refund, destination) = if is_sol_token(token_mint) {
+        (charge_vault.lamports(), authority.to_account_info())
+    } else {
+        let authority_token_account = authority_token_account
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        validate_token_account_ownership(authority_token_account, authority.key)?;
+        (
+            vault_available(token_mint, &charge_vault, vault_token_account)?,
+            authority_token_account.clone(),
+        )
+    };
+
+    if vault_refund > 0 {
+        withdraw_from_vault(
+            token_mint,
+            scheduled_charge_key,
+            &charge_vault,
+            vault_bump,
+            vault_token_account,
+            &destination,
+            token_program,
+            system_program,
+            vault_refund,
+        )?;
+    }
+
+    // Close the emptied vault token account and return its rent to the authority
+    if let (Some(vault_token_account), Some(token_program)) = (vault_token_account, token_program) {
+        close_spl_token_account_signed(
+            vault_token_account,
+            &authority.to_account_info(),
+            &charge_vault,
+            &token_program.to_account_info(),
+            &[&[b"charge_vault", scheduled_charge_key.as_ref(), &[vault_bump]]],
+        )?;
+    }
+
+    msg!("Charge vault refunded: {} lamports/tokens", vault_refund);
+
+    Ok(vault_refund)
+}
+
+/// Funds in a charge vault that are available for payouts
+/// SOL vaults keep the rent-exempt minimum in reserve so the vault account stays valid
+fn vault_available<'info>(
+    token_mint: &Pubkey,
+    charge_vault: &AccountInfo<'info>,
+    vault_token_account: Option<&AccountInfo<'info>>,
+) -> Result<u64> {
+    if is_sol_token(token_mint) {
+        return Ok(charge_vault.lamports().saturating_sub(Rent::get()?.minimum_balance(0)));
+    }
+
+    let vault_token_account = vault_token_account
+        .ok_or(BlinkPayError::InvalidChargeVault)?;
+
+    // SECURITY: Only count tokens the vault itself holds for this mint
+    validate_token_account_ownership(vault_token_account, charge_vault.key)?;
+    validate_token_account_mint(vault_token_account, token_mint)?;
+
+    token_account_balance(vault_token_account)
+}
+
+/// Move funds from the authority into a charge vault
+#[allow(clippy::too_many_arguments)]
+fn deposit_to_vault<'info>(
+    token_mint: &Pubkey,
+    authority: &AccountInfo<'info>,
+    charge_vault: &AccountInfo<'info>,
+    authority_token_account: Option<&AccountInfo<'info>>,
+    vault_token_account: Option<&AccountInfo<'info>>,
+    token_program: Option<&Program<'info, Token>>,
+    system_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if is_sol_token(token_mint) {
+        return transfer_sol(authority, charge_vault, amount, system_program);
+    }
+
+    let authority_token_account = authority_token_account
+        .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+    let vault_token_account = vault_token_account
+        .ok_or(BlinkPayError::InvalidChargeVault)?;
+    let token_program = token_program
+        .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+    // SECURITY: Vault token account must belong to the vault and hold the charge mint
+    validate_token_account_ownership(authority_token_account, authority.key)?;
+    validate_token_account_ownership(vault_token_account, charge_vault.key)?;
+    validate_token_account_mint(vault_token_account, token_mint)?;
+
+    transfer_spl_tokens(
+        authority_token_account,
+        vault_token_account,
+        authority,
+        &token_program.to_account_info(),
+        amount,
+    )
+}
+
+/// Move funds out of a charge vault, signing with the vault PDA seeds
+#[allow(clippy::too_many_arguments)]
+fn withdraw_from_vault<'info>(
+    token_mint: &Pubkey,
+    scheduled_charge: &Pubkey,
+    charge_vault: &AccountInfo<'info>,
+    vault_bump: u8,
+    vault_token_account: Option<&AccountInfo<'info>>,
+    destination: &AccountInfo<'info>,
+    token_program: Option<&Program<'info, Token>>,
+    system_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"charge_vault",
+        scheduled_charge.as_ref(),
+        &[vault_bump],
+    ]];
+
+    if is_sol_token(token_mint) {
+        return transfer_sol_signed(charge_vault, destination, amount, system_program, signer_seeds);
+    }
+
+    let vault_token_account = vault_token_account
+        .ok_or(BlinkPayError::InvalidChargeVault)?;
+    let token_program = token_program
+        .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+    transfer_spl_tokens_signed(
+        vault_token_account,
+        destination,
+        charge_vault,
+        &token_program.to_account_info(),
+        amount,
+        signer_seeds,
+    )
 }
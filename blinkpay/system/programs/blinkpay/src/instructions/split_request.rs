@@ -0,0 +1,310 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+
+use crate::emit_event;
+use crate::errors::BlinkPayError;
+use crate::events::*;
+use crate::state::{PaymentRequestStatus, SplitRequest, SplitShare, SplitShareInput, UserProfile};
+use crate::utils::*;
+
+/// Accounts required for creating a split request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(shares: Vec<SplitShareInput>)]
+pub struct CreateSplitRequest<'info> {
+    /// The authority creating the split request (payer of the rent)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The authority's profile, its counter indexes the new split request
+    #[account(
+        mut,
+        seeds = [b"user_profile", authority.key().as_ref()],
+        bump = user_profile.bump,
+        has_one = authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    /// The split request account to be created
+    #[account(
+        init,
+        payer = authority,
+        space = SplitRequest::space(shares.len()),
+        seeds = [
+            b"split_request",
+            authority.key().as_ref(),
+            &user_profile.split_request_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub split_request: Account<'info, SplitRequest>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for paying a share of a split request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PaySplitShare<'info> {
+    /// The payer settling their own share
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The split request account
+    #[account(
+        mut,
+        constraint = split_request.status == PaymentRequestStatus::Pending
+            || split_request.status == PaymentRequestStatus::PartiallyPaid @ BlinkPayError::PaymentRequestNotPending,
+    )]
+    pub split_request: Account<'info, SplitRequest>,
+
+    /// Recipient's SOL account (for SOL payments)
+    #[account(
+        mut,
+        constraint = is_sol_token(&split_request.token_mint) && recipient.key() == split_request.recipient
+    )]
+    pub recipient: Option<AccountInfo<'info>>,
+
+    /// Payer's token account (for SPL token payments)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&split_request.token_mint)
+    )]
+    pub payer_token_account: Option<AccountInfo<'info>>,
+
+    /// Recipient's token account (for SPL token payments)
+    #[account(
+        mut,
+        constraint = !is_sol_token(&split_request.token_mint)
+    )]
+    pub recipient_token_account: Option<AccountInfo<'info>>,
+
+    /// Token program (for SPL token payments)
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// System program (for SOL payments)
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for cancelling a split request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CancelSplitRequest<'info> {
+    /// The authority cancelling the request (must be the creator)
+    #[account(
+        constraint = authority.key() == split_request.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The split request account
+    #[account(
+        mut,
+        constraint = split_request.status == PaymentRequestStatus::Pending
+            || split_request.status == PaymentRequestStatus::PartiallyPaid @ BlinkPayError::PaymentRequestNotPending,
+    )]
+    pub split_request: Account<'info, SplitRequest>,
+}
+
+/// Accounts required for closing a settled split request
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseSplitRequest<'info> {
+    /// The authority closing the request (must be the creator, receives the rent)
+    #[account(
+        mut,
+        constraint = authority.key() == split_request.authority @ BlinkPayError::InvalidAuthority
+    )]
+    pub authority: Signer<'info>,
+
+    /// The split request account
+    #[account(
+        mut,
+        constraint = split_request.status == PaymentRequestStatus::Paid
+            || split_request.status == PaymentRequestStatus::Cancelled @ BlinkPayError::PaymentRequestNotSettled,
+        close = authority
+    )]
+    pub split_request: Account<'info, SplitRequest>,
+}
+
+/// Create a new split request
+/// Divides a bill between several payers who each settle their own share
+pub fn create_split_request(
+    ctx: Context<CreateSplitRequest>,
+    shares: Vec<SplitShareInput>,
+    token_mint: Pubkey,
+    recipient: Pubkey,
+    memo: String,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // SECURITY: Comprehensive input validation
+    let total_amount = validate_split_shares(&shares)?;
+    validate_token_mint(&token_mint)?;
+    validate_memo(&memo)?;
+    validate_recipient_not_authority(&recipient, ctx.accounts.authority.key)?;
+
+    // Advance the authority's counter so the next account gets a fresh index
+    let user_profile = &mut ctx.accounts.user_profile;
+    let index = user_profile.split_request_count;
+    user_profile.split_request_count = index.checked_add(1)
+        .ok_or(BlinkPayError::Overflow)?;
+
+    let split_request = &mut ctx.accounts.split_request;
+
+    // Initialize the split request
+    split_request.authority = *ctx.accounts.authority.key;
+    split_request.index = index;
+    split_request.recipient = recipient;
+    split_request.total_amount = total_amount;
+    split_request.amount_paid = 0;
+    split_request.token_mint = token_mint;
+    split_request.memo = memo;
+    split_request.created_at = current_time;
+    split_request.shares = shares
+        .iter()
+        .map(|share| SplitShare {
+            payer: share.payer,
+            amount: share.amount,
+            paid_at: None,
+        })
+        .collect();
+    split_request.status = PaymentRequestStatus::Pending;
+    split_request.bump = ctx.bumps.split_request;
+
+    msg!(
+        "Split request created: {} lamports/tokens across {} payers to {}",
+        total_amount,
+        split_request.shares.len(),
+        recipient
+    );
+
+    emit_event!(ctx, SplitRequestCreated {
+        split_request: split_request.key(),
+        index,
+        authority: split_request.authority,
+        recipient,
+        token_mint,
+        total_amount,
+        payers: split_request.shares.iter().map(|share| share.payer).collect(),
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Pay a share of a split request
+/// Each listed payer settles their own share, the request is paid once every share is settled
+pub fn pay_split_share(ctx: Context<PaySplitShare>) -> Result<()> {
+    let split_request = &mut ctx.accounts.split_request;
+    let payer_key = ctx.accounts.payer.key();
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let share = split_request
+        .shares
+        .iter_mut()
+        .find(|share| share.payer == payer_key)
+        .ok_or(BlinkPayError::PayerNotInSplit)?;
+
+    if share.paid_at.is_some() {
+        return err!(BlinkPayError::SplitShareAlreadyPaid);
+    }
+
+    // Record the share as paid first to prevent reentrancy
+    share.paid_at = Some(current_time);
+    let amount = share.amount;
+
+    split_request.amount_paid = safe_add(split_request.amount_paid, amount)?;
+    split_request.status = if split_request.shares.iter().all(|share| share.paid_at.is_some()) {
+        PaymentRequestStatus::Paid
+    } else {
+        PaymentRequestStatus::PartiallyPaid
+    };
+
+    if is_sol_token(&split_request.token_mint) {
+        // SOL payment
+        let recipient = ctx.accounts.recipient.as_ref()
+            .ok_or(BlinkPayError::InvalidRecipient)?;
+
+        transfer_sol(
+            &ctx.accounts.payer.to_account_info(),
+            recipient,
+            amount,
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        msg!("SOL split share paid: {} lamports by {}", amount, payer_key);
+    } else {
+        // SPL token payment
+        let payer_token_account = ctx.accounts.payer_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenAccountOwner)?;
+        let recipient_token_account = ctx.accounts.recipient_token_account.as_ref()
+            .ok_or(BlinkPayError::InvalidAssociatedTokenAccount)?;
+        let token_program = ctx.accounts.token_program.as_ref()
+            .ok_or(BlinkPayError::InvalidTokenMint)?;
+
+        // Validate token account ownership, the share must land in the recipient's account for the request's mint
+        validate_token_account_ownership(payer_token_account, &payer_key)?;
+        validate_token_account_ownership(recipient_token_account, &split_request.recipient)?;
+        validate_token_account_mint(recipient_token_account, &split_request.token_mint)?;
+
+        // Transfer tokens
+        transfer_spl_tokens(
+            payer_token_account,
+            recipient_token_account,
+            &ctx.accounts.payer.to_account_info(),
+            &token_program.to_account_info(),
+            amount,
+        )?;
+
+        msg!("SPL token split share paid: {} tokens by {}", amount, payer_key);
+    }
+
+    emit_event!(ctx, SplitSharePaid {
+        split_request: split_request.key(),
+        payer: payer_key,
+        recipient: split_request.recipient,
+        token_mint: split_request.token_mint,
+        amount,
+        amount_paid: split_request.amount_paid,
+        status: split_request.status.clone(),
+        timestamp: current_time,
+    });
+
+    Ok(())
+}
+
+/// Cancel a split request
+/// Only the authority can cancel their own unsettled requests
+pub fn cancel_split_request(ctx: Context<CancelSplitRequest>) -> Result<()> {
+    let split_request = &mut ctx.accounts.split_request;
+
+    // Mark as cancelled so remaining shares can no longer be paid
+    split_request.status = PaymentRequestStatus::Cancelled;
+
+    msg!("Split request cancelled by authority");
+
+    emit_event!(ctx, SplitRequestCancelled {
+        split_request: split_request.key(),
+        authority: split_request.authority,
+        amount_paid: split_request.amount_paid,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Close a paid or cancelled split request
+/// Returns the account rent to the authority
+pub fn close_split_request(ctx: Context<CloseSplitRequest>) -> Result<()> {
+    msg!("Split request closed, rent returned to authority");
+
+    emit_event!(ctx, SplitRequestClosed {
+        split_request: ctx.accounts.split_request.key(),
+        authority: ctx.accounts.authority.key(),
+        status: ctx.accounts.split_request.status.clone(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use crate::emit_event;
+use crate::events::*;
+use crate::state::UserProfile;
+
+/// Accounts required for initializing a user profile
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct InitializeUserProfile<'info> {
+    /// The owner of the profile
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// The user profile account to be created
+    #[account(
+        init,
+        payer = authority,
+        space = UserProfile::LEN,
+        seeds = [b"user_profile", authority.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    /// System program for account creation
+    pub system_program: Program<'info, System>,
+}
+
+/// Initialize a user profile
+/// Holds the counters used to derive the user's payment requests and scheduled charges
+pub fn initialize_user_profile(ctx: Context<InitializeUserProfile>) -> Result<()> {
+    let user_profile = &mut ctx.accounts.user_profile;
+
+    user_profile.authority = *ctx.accounts.authority.key;
+    user_profile.payment_request_count = 0;
+    user_profile.split_request_count = 0;
+    user_profile.scheduled_charge_count = 0;
+    user_profile.plan_count = 0;
+    user_profile.bump = ctx.bumps.user_profile;
+
+    msg!("User profile initialized for {}", user_profile.authority);
+
+    emit_event!(ctx, UserProfileInitialized {
+        user_profile: user_profile.key(),
+        authority: user_profile.authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
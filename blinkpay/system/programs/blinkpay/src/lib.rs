@@ -3,9 +3,12 @@ use anchor_lang::prelude::*;
 pub mod state;
 pub mod instructions;
 pub mod errors;
+pub mod events;
+pub mod schedule;
 pub mod utils;
 
 use instructions::*;
+use state::{CancellationReason, ProrationSettlement, Recurrence, ScheduledChargeParams, SplitShareInput};
 
 declare_id!("9zMTynBadkbNVsjujpxkgzXGCezDkvrqZxMtj98T961o");
 
@@ -13,51 +16,128 @@ declare_id!("9zMTynBadkbNVsjujpxkgzXGCezDkvrqZxMtj98T961o");
 pub mod blinkpay {
     use super::*;
 
+    /// Initialize a user profile
+    /// Holds the counters used to derive the user's payment requests and scheduled charges
+    pub fn initialize_user_profile(ctx: Context<InitializeUserProfile>) -> Result<()> {
+        instructions::initialize_user_profile(ctx)
+    }
+
     /// Create a new payment request
     /// Allows users to request payments that can be fulfilled by anyone
+    #[allow(clippy::too_many_arguments)]
     pub fn create_payment_request(
         ctx: Context<CreatePaymentRequest>,
         amount: u64,
         token_mint: Pubkey,
         recipient: Pubkey,
         memo: String,
-        current_time: i64,
+        expires_at: Option<i64>,
+        allow_partial: bool,
+        designated_payer: Option<Pubkey>,
+        escrow_timeout_seconds: Option<u64>,
     ) -> Result<()> {
-        instructions::create_payment_request(ctx, amount, token_mint, recipient, memo, current_time)
+        instructions::create_payment_request(
+            ctx,
+            amount,
+            token_mint,
+            recipient,
+            memo,
+            expires_at,
+            allow_partial,
+            designated_payer,
+            escrow_timeout_seconds,
+        )
     }
 
     /// Pay a payment request
-    /// Anyone can pay a pending payment request to fulfill it
+    /// Anyone can pay a pending payment request to fulfill it, unless a designated payer is set
     pub fn pay_request(ctx: Context<PayRequest>) -> Result<()> {
         instructions::pay_request(ctx)
     }
 
-    /// Create a new scheduled charge
-    /// Sets up automatic payments that execute at specified times
-    pub fn create_scheduled_charge(
-        ctx: Context<CreateScheduledCharge>,
+    /// Pay part of a payment request
+    /// Sends any amount up to the outstanding balance on requests that allow partial payments
+    pub fn pay_request_partial(ctx: Context<PayRequest>, amount: u64) -> Result<()> {
+        instructions::pay_request_partial(ctx, amount)
+    }
+
+    /// Release an escrowed payment request
+    /// The payer or the authority releases the escrowed funds to the recipient
+    pub fn release_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+        instructions::release_escrow(ctx)
+    }
+
+    /// Refund an escrowed payment request
+    /// The authority can refund the payer at any time, anyone can once the escrow timeout has passed
+    pub fn refund_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+        instructions::refund_escrow(ctx)
+    }
+
+    /// Refund a paid payment request
+    /// The recipient sends all or part of a payer's contribution back to that payer
+    pub fn refund_payment_request(
+        ctx: Context<RefundPaymentRequest>,
+        payer: Pubkey,
         amount: u64,
+    ) -> Result<()> {
+        instructions::refund_payment_request(ctx, payer, amount)
+    }
+
+    /// Cancel a payment request
+    /// Only the authority can cancel their own pending requests
+    pub fn cancel_payment_request(ctx: Context<CancelPaymentRequest>) -> Result<()> {
+        instructions::cancel_payment_request(ctx)
+    }
+
+    /// Close a payment request
+    /// Reclaims rent from paid, refunded or fully refunded cancelled requests back to the authority
+    pub fn close_payment_request(ctx: Context<ClosePaymentRequest>) -> Result<()> {
+        instructions::close_payment_request(ctx)
+    }
+
+    /// Close an expired payment request
+    /// Anyone can close a pending request past its expiry, rent goes back to the authority
+    pub fn close_expired_payment_request(ctx: Context<CloseExpiredPaymentRequest>) -> Result<()> {
+        instructions::close_expired_payment_request(ctx)
+    }
+
+    /// Create a new split request
+    /// Divides a bill between several payers who each settle their own share
+    pub fn create_split_request(
+        ctx: Context<CreateSplitRequest>,
+        shares: Vec<SplitShareInput>,
         token_mint: Pubkey,
         recipient: Pubkey,
-        execute_at: i64,
-        charge_type: u8,
-        interval_seconds: Option<u64>,
-        max_executions: Option<u32>,
         memo: String,
-        current_time: i64,
     ) -> Result<()> {
-        instructions::create_scheduled_charge(
-            ctx,
-            amount,
-            token_mint,
-            recipient,
-            execute_at,
-            charge_type,
-            interval_seconds,
-            max_executions,
-            memo,
-            current_time,
-        )
+        instructions::create_split_request(ctx, shares, token_mint, recipient, memo)
+    }
+
+    /// Pay a share of a split request
+    /// Only a listed payer can settle their own share
+    pub fn pay_split_share(ctx: Context<PaySplitShare>) -> Result<()> {
+        instructions::pay_split_share(ctx)
+    }
+
+    /// Cancel a split request
+    /// Only the authority can cancel their own unsettled requests
+    pub fn cancel_split_request(ctx: Context<CancelSplitRequest>) -> Result<()> {
+        instructions::cancel_split_request(ctx)
+    }
+
+    /// Close a split request
+    /// Reclaims rent from paid or cancelled split requests back to the authority
+    pub fn close_split_request(ctx: Context<CloseSplitRequest>) -> Result<()> {
+        instructions::close_split_request(ctx)
+    }
+
+    /// Create a new scheduled charge
+    /// Sets up automatic payments that execute at specified times
+    pub fn create_scheduled_charge(
+        ctx: Context<CreateScheduledCharge>,
+        params: ScheduledChargeParams,
+    ) -> Result<()> {
+        instructions::create_scheduled_charge(ctx, params)
     }
 
     /// Execute a scheduled charge
@@ -67,9 +147,113 @@ pub mod blinkpay {
     }
 
     /// Cancel a scheduled charge
-    /// Only the authority can cancel their own charges
-    pub fn cancel_scheduled_charge(ctx: Context<CancelScheduledCharge>) -> Result<()> {
-        instructions::cancel_scheduled_charge(ctx)
+    /// The authority or the recipient can cancel, the rent always returns to the authority
+    pub fn cancel_scheduled_charge(
+        ctx: Context<CancelScheduledCharge>,
+        reason: CancellationReason,
+    ) -> Result<()> {
+        instructions::cancel_scheduled_charge(ctx, reason)
+    }
+
+    /// Pause a scheduled charge
+    /// Only the authority can pause, optionally resuming automatically at a set time
+    pub fn pause_scheduled_charge(
+        ctx: Context<PauseScheduledCharge>,
+        auto_resume_at: Option<i64>,
+    ) -> Result<()> {
+        instructions::pause_scheduled_charge(ctx, auto_resume_at)
+    }
+
+    /// Resume a paused scheduled charge
+    /// Reschedules the charge to its next slot without charging for the paused periods
+    pub fn resume_scheduled_charge(ctx: Context<ResumeScheduledCharge>) -> Result<()> {
+        instructions::resume_scheduled_charge(ctx)
+    }
+
+    /// Propose new terms for a scheduled charge
+    /// Either party may propose, the terms only take effect once the other party accepts
+    pub fn propose_charge_amendment(
+        ctx: Context<ProposeChargeAmendment>,
+        amount: u64,
+        recurrence: Option<Recurrence>,
+        max_executions: Option<u32>,
+        recipient: Pubkey,
+    ) -> Result<()> {
+        instructions::propose_charge_amendment(ctx, amount, recurrence, max_executions, recipient)
+    }
+
+    /// Accept proposed terms for a scheduled charge
+    /// Only the counterparty of the proposal can accept, the terms apply from the next execution
+    pub fn accept_charge_amendment(
+        ctx: Context<AcceptChargeAmendment>,
+        amount: u64,
+        recurrence: Option<Recurrence>,
+        max_executions: Option<u32>,
+        recipient: Pubkey,
+    ) -> Result<()> {
+        instructions::accept_charge_amendment(ctx, amount, recurrence, max_executions, recipient)
+    }
+
+    /// Reject proposed terms for a scheduled charge
+    /// The counterparty rejects or the proposer withdraws, the current terms stay in place
+    pub fn reject_charge_amendment(ctx: Context<RejectChargeAmendment>) -> Result<()> {
+        instructions::reject_charge_amendment(ctx)
+    }
+
+    /// Top up a vault-funded scheduled charge
+    /// Adds funds to the charge vault for future executions
+    pub fn top_up_scheduled_charge(ctx: Context<TopUpScheduledCharge>, amount: u64) -> Result<()> {
+        instructions::top_up_scheduled_charge(ctx, amount)
+    }
+
+    /// Withdraw unused funds from a scheduled charge vault
+    /// Funds committed to the remaining capped executions, or the next one if uncapped, stay in the vault
+    pub fn withdraw_unused(ctx: Context<WithdrawUnused>) -> Result<()> {
+        instructions::withdraw_unused(ctx)
+    }
+
+    /// Report usage against a metered scheduled charge
+    /// The recipient or the meter authority adds units billed on the next execution
+    pub fn report_usage(ctx: Context<ReportUsage>, units: u64) -> Result<()> {
+        instructions::report_usage(ctx, units)
+    }
+
+    /// Close a scheduled charge that has passed its end date
+    /// Anyone can close, the rent and any vault balance return to the authority
+    pub fn close_expired_charge(ctx: Context<CloseExpiredCharge>) -> Result<()> {
+        instructions::close_expired_charge(ctx)
+    }
+
+    /// Create a subscription plan
+    /// Merchants define the price and billing schedule once and subscribers sign up to it
+    pub fn create_plan(
+        ctx: Context<CreatePlan>,
+        amount: u64,
+        token_mint: Pubkey,
+        recurrence: Recurrence,
+        trial_seconds: Option<u64>,
+        max_executions: Option<u32>,
+        memo: String,
+    ) -> Result<()> {
+        instructions::create_plan(ctx, amount, token_mint, recurrence, trial_seconds, max_executions, memo)
+    }
+
+    /// Open or close a plan to new subscribers
+    /// Existing subscriptions keep running either way
+    pub fn set_plan_active(ctx: Context<SetPlanActive>, active: bool) -> Result<()> {
+        instructions::set_plan_active(ctx, active)
+    }
+
+    /// Subscribe to a plan
+    /// Creates a scheduled charge from the plan's terms, billed from now with any trial cycles free
+    pub fn subscribe(ctx: Context<Subscribe>) -> Result<()> {
+        instructions::subscribe(ctx)
+    }
+
+    /// Move a subscription to another plan of the same merchant
+    /// The price difference for the rest of the current period is settled now or carried into the next execution
+    pub fn change_plan(ctx: Context<ChangePlan>, settlement: ProrationSettlement) -> Result<()> {
+        instructions::change_plan(ctx, settlement)
     }
 }
 
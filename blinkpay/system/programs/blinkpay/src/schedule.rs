@@ -0,0 +1,410 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::BlinkPayError;
+use crate::state::Recurrence;
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+pub const DAYS_PER_WEEK: i64 = 7;
+
+/// Check if a year is a leap year in the proleptic Gregorian calendar
+pub fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Number of days in a month (1-12) of the given year
+pub fn days_in_month(year: i64, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch for a civil date
+/// Uses Howard Hinnant's days_from_civil algorithm, valid for any year
+pub fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Civil date (year, month, day) for a number of days since the Unix epoch
+/// Inverse of `days_from_civil`
+pub fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u8;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Day of the week for a number of days since the Unix epoch (0 = Monday)
+pub fn weekday(days: i64) -> u8 {
+    // 1970-01-01 was a Thursday
+    (days + 3).rem_euclid(DAYS_PER_WEEK) as u8
+}
+
+/// Compute the first occurrence of a recurrence strictly after `previous`
+/// Calendar recurrences keep the time of day of `previous` and clamp the day to the month length
+pub fn next_occurrence(recurrence: &Recurrence, previous: i64) -> Result<i64> {
+    let days = previous.div_euclid(SECONDS_PER_DAY);
+    let time_of_day = previous.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+
+    let next_days = match *recurrence {
+        Recurrence::EveryNSeconds { interval_seconds } => {
+            return previous.checked_add(interval_seconds as i64)
+                .ok_or(BlinkPayError::Overflow.into());
+        }
+        Recurrence::Weekly { weekday: target } => {
+            let ahead = (target as i64 - weekday(days) as i64).rem_euclid(DAYS_PER_WEEK);
+            days + if ahead == 0 { DAYS_PER_WEEK } else { ahead }
+        }
+        Recurrence::Monthly { day_of_month } => {
+            let this_month = day_of_month.min(days_in_month(year, month));
+            if this_month > day {
+                days_from_civil(year, month, this_month)
+            } else {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                days_from_civil(year, month, day_of_month.min(days_in_month(year, month)))
+            }
+        }
+        Recurrence::Yearly { month: target_month, day: target_day } => {
+            let this_year = target_day.min(days_in_month(year, target_month));
+            if (target_month, this_year) > (month, day) {
+                days_from_civil(year, target_month, this_year)
+            } else {
+                let year = year + 1;
+                days_from_civil(year, target_month, target_day.min(days_in_month(year, target_month)))
+            }
+        }
+    };
+
+    next_days.checked_mul(SECONDS_PER_DAY)
+        .and_then(|seconds| seconds.checked_add(time_of_day))
+        .ok_or(BlinkPayError::Overflow.into())
+}
+
+/// Compute the occurrence one period before `next` on the same schedule
+/// Calendar recurrences keep the time of day of `next` and clamp the day to the month length
+pub fn previous_occurrence(recurrence: &Recurrence, next: i64) -> Result<i64> {
+    let days = next.div_euclid(SECONDS_PER_DAY);
+    let time_of_day = next.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, _) = civil_from_days(days);
+
+    let previous_days = match *recurrence {
+        Recurrence::EveryNSeconds { interval_seconds } => {
+            return next.checked_sub(interval_seconds as i64)
+                .ok_or(BlinkPayError::Overflow.into());
+        }
+        Recurrence::Weekly { .. } => days - DAYS_PER_WEEK,
+        Recurrence::Monthly { day_of_month } => {
+            let (year, month) = if month == 1 { (year - 1, 12) } else { (year, month - 1) };
+            days_from_civil(year, month, day_of_month.min(days_in_month(year, month)))
+        }
+        Recurrence::Yearly { month: target_month, day: target_day } => {
+            let year = year - 1;
+            days_from_civil(year, target_month, target_day.min(days_in_month(year, target_month)))
+        }
+    };
+
+    previous_days.checked_mul(SECONDS_PER_DAY)
+        .and_then(|seconds| seconds.checked_add(time_of_day))
+        .ok_or(BlinkPayError::Overflow.into())
+}
+
+/// Length of the period for recurrences that repeat at a fixed interval
+/// Calendar months and years vary in length and return None
+fn fixed_period(recurrence: &Recurrence) -> Option<i64> {
+    match *recurrence {
+        Recurrence::EveryNSeconds { interval_seconds } => Some(interval_seconds as i64),
+        Recurrence::Weekly { .. } => Some(DAYS_PER_WEEK * SECONDS_PER_DAY),
+        Recurrence::Monthly { .. } | Recurrence::Yearly { .. } => None,
+    }
+}
+
+/// Count the occurrences strictly after `from` and at or before `to`
+pub fn periods_between(recurrence: &Recurrence, from: i64, to: i64) -> Result<u64> {
+    let mut next = next_occurrence(recurrence, from)?;
+    if next > to {
+        return Ok(0);
+    }
+
+    // Fixed-length periods can be counted directly
+    if let Some(period) = fixed_period(recurrence) {
+        return Ok(((to - next) / period + 1) as u64);
+    }
+
+    // Calendar months vary in length, so step one occurrence at a time
+    let mut periods = 0;
+    while next <= to {
+        periods += 1;
+        next = next_occurrence(recurrence, next)?;
+    }
+
+    Ok(periods)
+}
+
+/// First occurrence of the schedule anchored at `anchor` that falls at or after `from`
+pub fn first_occurrence_from(recurrence: &Recurrence, anchor: i64, from: i64) -> Result<i64> {
+    if anchor >= from {
+        return Ok(anchor);
+    }
+
+    // Skip every occurrence that falls before `from`
+    let earlier = periods_between(recurrence, anchor, from - 1)?;
+    advance_periods(recurrence, anchor, earlier + 1)
+}
+
+/// Compute the `n`th occurrence after `from`, or `from` itself when `n` is zero
+pub fn advance_periods(recurrence: &Recurrence, from: i64, n: u64) -> Result<i64> {
+    if n == 0 {
+        return Ok(from);
+    }
+
+    let first = next_occurrence(recurrence, from)?;
+
+    // Fixed-length periods can jump straight to the target
+    if let Some(period) = fixed_period(recurrence) {
+        return (n as i64 - 1).checked_mul(period)
+            .and_then(|offset| first.checked_add(offset))
+            .ok_or(BlinkPayError::Overflow.into());
+    }
+
+    // Calendar months vary in length, so step one occurrence at a time
+    let mut next = first;
+    for _ in 1..n {
+        next = next_occurrence(recurrence, next)?;
+    }
+
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix timestamp for a civil date and time of day
+    fn timestamp(year: i64, month: u8, day: u8, seconds: i64) -> i64 {
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + seconds
+    }
+
+    /// Successive occurrences after `from`
+    fn occurrences(recurrence: &Recurrence, from: i64, count: usize) -> Vec<i64> {
+        let mut next = from;
+        (0..count)
+            .map(|_| {
+                next = next_occurrence(recurrence, next).unwrap();
+                next
+            })
+            .collect()
+    }
+
+    #[test]
+    fn leap_years_follow_the_gregorian_rules() {
+        assert!(is_leap_year(2024));
+        assert!(is_leap_year(2000));
+        assert!(!is_leap_year(1900));
+        assert!(!is_leap_year(2025));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2025, 2), 28);
+        assert_eq!(days_in_month(2025, 4), 30);
+        assert_eq!(days_in_month(2025, 12), 31);
+    }
+
+    #[test]
+    fn civil_dates_round_trip_through_epoch_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+
+        for days in (-800_000..800_000).step_by(97) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn weekdays_count_from_monday() {
+        // 1970-01-01 was a Thursday and 2024-01-01 a Monday
+        assert_eq!(weekday(0), 3);
+        assert_eq!(weekday(days_from_civil(2024, 1, 1)), 0);
+        assert_eq!(weekday(-1), 2);
+    }
+
+    #[test]
+    fn monthly_clamps_to_short_months_and_returns_to_the_anchor_day() {
+        let monthly = Recurrence::Monthly { day_of_month: 31 };
+        let noon = SECONDS_PER_DAY / 2;
+
+        assert_eq!(
+            occurrences(&monthly, timestamp(2024, 1, 31, noon), 3),
+            vec![
+                timestamp(2024, 2, 29, noon),
+                timestamp(2024, 3, 31, noon),
+                timestamp(2024, 4, 30, noon),
+            ]
+        );
+        assert_eq!(
+            occurrences(&monthly, timestamp(2025, 1, 31, noon), 2),
+            vec![timestamp(2025, 2, 28, noon), timestamp(2025, 3, 31, noon)]
+        );
+        assert_eq!(
+            occurrences(&monthly, timestamp(2025, 12, 31, 0), 1),
+            vec![timestamp(2026, 1, 31, 0)]
+        );
+    }
+
+    #[test]
+    fn yearly_leap_day_falls_back_outside_leap_years() {
+        let leap_day = Recurrence::Yearly { month: 2, day: 29 };
+
+        assert_eq!(
+            occurrences(&leap_day, timestamp(2024, 2, 29, 0), 4),
+            vec![
+                timestamp(2025, 2, 28, 0),
+                timestamp(2026, 2, 28, 0),
+                timestamp(2027, 2, 28, 0),
+                timestamp(2028, 2, 29, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn weekly_wraps_across_month_and_year_ends() {
+        let mondays = Recurrence::Weekly { weekday: 0 };
+
+        // Wednesday 2025-12-31 rolls into the new year
+        assert_eq!(
+            occurrences(&mondays, timestamp(2025, 12, 31, 3_600), 2),
+            vec![timestamp(2026, 1, 5, 3_600), timestamp(2026, 1, 12, 3_600)]
+        );
+        // A Monday moves a full week ahead, across the end of February
+        assert_eq!(
+            occurrences(&mondays, timestamp(2024, 2, 26, 0), 1),
+            vec![timestamp(2024, 3, 4, 0)]
+        );
+    }
+
+    #[test]
+    fn previous_occurrence_steps_back_one_period() {
+        let noon = SECONDS_PER_DAY / 2;
+
+        let hourly = Recurrence::EveryNSeconds { interval_seconds: 3_600 };
+        assert_eq!(previous_occurrence(&hourly, 7_200).unwrap(), 3_600);
+
+        let mondays = Recurrence::Weekly { weekday: 0 };
+        assert_eq!(
+            previous_occurrence(&mondays, timestamp(2026, 1, 5, noon)).unwrap(),
+            timestamp(2025, 12, 29, noon)
+        );
+
+        // Month ends clamp to the shorter month, in both directions across a year end
+        let monthly = Recurrence::Monthly { day_of_month: 31 };
+        assert_eq!(
+            previous_occurrence(&monthly, timestamp(2024, 3, 31, noon)).unwrap(),
+            timestamp(2024, 2, 29, noon)
+        );
+        assert_eq!(
+            previous_occurrence(&monthly, timestamp(2024, 2, 29, noon)).unwrap(),
+            timestamp(2024, 1, 31, noon)
+        );
+        assert_eq!(
+            previous_occurrence(&monthly, timestamp(2026, 1, 31, 0)).unwrap(),
+            timestamp(2025, 12, 31, 0)
+        );
+
+        let leap_day = Recurrence::Yearly { month: 2, day: 29 };
+        assert_eq!(
+            previous_occurrence(&leap_day, timestamp(2025, 2, 28, 0)).unwrap(),
+            timestamp(2024, 2, 29, 0)
+        );
+    }
+
+    #[test]
+    fn previous_occurrence_inverts_next_occurrence_on_schedule() {
+        let schedules = [
+            Recurrence::EveryNSeconds { interval_seconds: 3_600 },
+            Recurrence::Weekly { weekday: 4 },
+            Recurrence::Monthly { day_of_month: 31 },
+            Recurrence::Yearly { month: 2, day: 29 },
+        ];
+
+        for recurrence in &schedules {
+            let first = next_occurrence(recurrence, timestamp(2023, 6, 15, 45)).unwrap();
+            let slots = occurrences(recurrence, first, 6);
+            let mut previous = first;
+            for slot in slots {
+                assert_eq!(previous_occurrence(recurrence, slot).unwrap(), previous);
+                previous = slot;
+            }
+        }
+    }
+
+    #[test]
+    fn periods_between_counts_exact_boundaries() {
+        let hourly = Recurrence::EveryNSeconds { interval_seconds: 3_600 };
+        assert_eq!(periods_between(&hourly, 0, 3_599).unwrap(), 0);
+        assert_eq!(periods_between(&hourly, 0, 3_600).unwrap(), 1);
+        assert_eq!(periods_between(&hourly, 0, 7_199).unwrap(), 1);
+        assert_eq!(periods_between(&hourly, 0, 7_200).unwrap(), 2);
+
+        let monthly = Recurrence::Monthly { day_of_month: 31 };
+        let from = timestamp(2024, 1, 31, 0);
+        let february = timestamp(2024, 2, 29, 0);
+        assert_eq!(periods_between(&monthly, from, february - 1).unwrap(), 0);
+        assert_eq!(periods_between(&monthly, from, february).unwrap(), 1);
+        assert_eq!(periods_between(&monthly, from, timestamp(2024, 3, 31, 0)).unwrap(), 2);
+        assert_eq!(periods_between(&monthly, from, from).unwrap(), 0);
+    }
+
+    #[test]
+    fn advance_periods_matches_stepping_one_occurrence_at_a_time() {
+        let schedules = [
+            Recurrence::EveryNSeconds { interval_seconds: 3_600 },
+            Recurrence::Weekly { weekday: 4 },
+            Recurrence::Monthly { day_of_month: 31 },
+            Recurrence::Yearly { month: 2, day: 29 },
+        ];
+        let from = timestamp(2024, 1, 31, 45);
+
+        for recurrence in &schedules {
+            assert_eq!(advance_periods(recurrence, from, 0).unwrap(), from);
+            let stepped = occurrences(recurrence, from, 5);
+            for (n, expected) in stepped.iter().enumerate() {
+                assert_eq!(advance_periods(recurrence, from, n as u64 + 1).unwrap(), *expected);
+            }
+        }
+    }
+
+    #[test]
+    fn first_occurrence_from_skips_earlier_slots() {
+        let monthly = Recurrence::Monthly { day_of_month: 31 };
+        let anchor = timestamp(2024, 1, 31, 0);
+
+        assert_eq!(first_occurrence_from(&monthly, anchor, anchor - 1).unwrap(), anchor);
+        assert_eq!(
+            first_occurrence_from(&monthly, anchor, timestamp(2024, 2, 29, 0)).unwrap(),
+            timestamp(2024, 2, 29, 0)
+        );
+        assert_eq!(
+            first_occurrence_from(&monthly, anchor, timestamp(2024, 3, 1, 0)).unwrap(),
+            timestamp(2024, 3, 31, 0)
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use anchor_lang::prelude::*;
 
+use crate::utils::MAX_REQUEST_PAYERS;
+
 /// Status of a payment request
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
 pub enum PaymentRequestStatus {
     Pending,
+    PartiallyPaid,
     Paid,
     Cancelled,
+    Escrowed,
+    Refunded,
+    PartiallyRefunded,
 }
 
 /// Status of a scheduled charge
@@ -14,6 +20,10 @@ pub enum ScheduledChargeStatus {
     Pending,
     Executed,
     Cancelled,
+    Paused,
+    Expired,
+    PastDue,
+    Suspended,
 }
 
 /// Type of scheduled charge
@@ -21,53 +31,333 @@ pub enum ScheduledChargeStatus {
 pub enum ScheduledChargeType {
     OneTime,
     Recurring,
+    Metered,
+}
+
+/// When a recurring scheduled charge repeats
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    /// Fixed interval between executions
+    EveryNSeconds { interval_seconds: u64 },
+    /// Every week on a weekday (0 = Monday, 6 = Sunday)
+    Weekly { weekday: u8 },
+    /// Every month on a day (1-31), clamped to the last day of shorter months
+    Monthly { day_of_month: u8 },
+    /// Every year on a date, February 29 falls back to February 28 outside leap years
+    Yearly { month: u8, day: u8 },
+}
+
+/// What a late recurring charge does about periods it missed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Charge every missed period, one per execution, until the schedule is current
+    ExecuteAll,
+    /// Charge only the latest due period and drop the ones before it
+    SkipMissed,
+    /// Charge at most `max_periods` missed periods and drop any older ones
+    Capped { max_periods: u32 },
+}
+
+/// Reduced price charged for the first executions of a recurring charge
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct IntroductoryOffer {
+    /// Amount charged per execution while the offer lasts
+    pub amount: u64,
+    /// Number of executions charged at the introductory amount
+    pub executions: u32,
+}
+
+impl IntroductoryOffer {
+    pub const LEN: usize = 8 + // amount
+        4; // executions
+}
+
+/// Usage metering for a metered scheduled charge
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct Meter {
+    /// Price billed per unit of reported usage
+    pub unit_price: u64,
+    /// Account allowed to report usage besides the recipient
+    pub meter_authority: Pubkey,
+    /// Units reported since the last execution
+    pub usage: u64,
+}
+
+impl Meter {
+    pub const LEN: usize = 8 + // unit_price
+        32 + // meter_authority
+        8; // usage
+}
+
+/// New terms proposed for a scheduled charge, applied once the counterparty accepts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct ChargeAmendment {
+    /// The party that proposed the terms, the authority (payer) or the recipient (merchant)
+    pub proposed_by: Pubkey,
+    /// Proposed amount per execution
+    pub amount: u64,
+    /// Proposed recurrence
+    pub recurrence: Option<Recurrence>,
+    /// Proposed maximum number of executions
+    pub max_executions: Option<u32>,
+    /// Proposed recipient
+    pub recipient: Pubkey,
+    /// Timestamp when the terms were proposed
+    pub proposed_at: i64,
+}
+
+/// Why a scheduled charge was cancelled, recorded in the cancellation event
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum CancellationReason {
+    /// No reason given
+    Unspecified,
+    /// The payer asked to stop the charge
+    CustomerRequest,
+    /// The merchant no longer provides the service
+    ServiceDiscontinued,
+    /// Payments could not be collected
+    PaymentFailure,
+    /// The charge was found to be fraudulent
+    Fraud,
+    /// Any other reason
+    Other,
+}
+
+/// When the prorated difference of a plan change is paid
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum ProrationSettlement {
+    /// Charge the difference now, credits are always carried to the next execution
+    Immediate,
+    /// Add the difference to, or take it off, the next execution
+    NextExecution,
+}
+
+/// How a scheduled charge is funded
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum ChargeFundingMode {
+    /// Pulled from the authority at execution time (SOL signed by the authority, SPL through the charge delegate)
+    Direct,
+    /// Paid out of funds deposited up front in the charge vault
+    Vault,
+}
+
+/// User profile account
+/// Holds per-user counters so every account the user creates gets a deterministic, enumerable address
+#[account]
+pub struct UserProfile {
+    /// The owner of the profile
+    pub authority: Pubkey,
+    /// Number of payment requests created (index of the next one)
+    pub payment_request_count: u64,
+    /// Number of split requests created (index of the next one)
+    pub split_request_count: u64,
+    /// Number of scheduled charges created (index of the next one)
+    pub scheduled_charge_count: u64,
+    /// Number of subscription plans created (index of the next one)
+    pub plan_count: u64,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
 }
 
 /// Payment request account
-/// Stores information about a payment request that can be paid by anyone
+/// Stores information about a payment request that can be paid by anyone, or only by a designated payer
 #[account]
 pub struct PaymentRequest {
     /// The creator/owner of the payment request
     pub authority: Pubkey,
+    /// Index from the authority's profile counter, used to derive the account address
+    pub index: u64,
     /// The recipient who should receive the payment
     pub recipient: Pubkey,
     /// Amount to be paid (in smallest units)
     pub amount: u64,
+    /// Amount paid so far (in smallest units)
+    pub amount_paid: u64,
+    /// Amount refunded to the payer so far (in smallest units)
+    pub amount_refunded: u64,
+    /// Whether payers may settle the request in several instalments
+    pub allow_partial: bool,
+    /// Only this payer may settle the request (None lets anyone pay)
+    pub designated_payer: Option<Pubkey>,
+    /// The most recent payer (None until paid), the sole payer of escrowed requests
+    pub paid_by: Option<Pubkey>,
+    /// Timestamp of the most recent payment (None until paid)
+    pub paid_at: Option<i64>,
+    /// Amount paid and refunded per payer, so each instalment can be refunded to whoever sent it
+    pub payments: Vec<PaymentContribution>,
+    /// For escrowed requests: how long funds stay locked after payment (in seconds)
+    pub escrow_timeout_seconds: Option<u64>,
+    /// For escrowed requests: timestamp after which the escrow can be refunded by anyone
+    pub escrow_expires_at: Option<i64>,
     /// Token mint (Pubkey::default() for SOL)
     pub token_mint: Pubkey,
     /// Optional memo/description
     pub memo: String,
     /// Timestamp when request was created
     pub created_at: i64,
+    /// Timestamp after which the request can no longer be paid (None if it never expires)
+    pub expires_at: Option<i64>,
     /// Status of the payment request
     pub status: PaymentRequestStatus,
     /// Bump seed for PDA derivation
     pub bump: u8,
 }
 
+/// A single payer's contributions to a payment request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct PaymentContribution {
+    /// The payer who sent the funds
+    pub payer: Pubkey,
+    /// Amount paid by this payer (in smallest units)
+    pub amount: u64,
+    /// Amount refunded to this payer so far (in smallest units)
+    pub amount_refunded: u64,
+}
+
+/// A single payer's share of a split request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct SplitShare {
+    /// The payer responsible for this share
+    pub payer: Pubkey,
+    /// Amount owed by the payer (in smallest units)
+    pub amount: u64,
+    /// Timestamp when the share was paid (None if still outstanding)
+    pub paid_at: Option<i64>,
+}
+
+/// Share definition supplied when creating a split request
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct SplitShareInput {
+    /// The payer responsible for this share
+    pub payer: Pubkey,
+    /// Amount owed by the payer (in smallest units)
+    pub amount: u64,
+}
+
+/// Split request account
+/// Stores a bill divided between several payers, each settling their own share
+#[account]
+pub struct SplitRequest {
+    /// The creator/owner of the split request
+    pub authority: Pubkey,
+    /// Index from the authority's profile counter, used to derive the account address
+    pub index: u64,
+    /// The recipient who should receive the payments
+    pub recipient: Pubkey,
+    /// Sum of all shares (in smallest units)
+    pub total_amount: u64,
+    /// Amount paid so far across all shares (in smallest units)
+    pub amount_paid: u64,
+    /// Token mint (Pubkey::default() for SOL)
+    pub token_mint: Pubkey,
+    /// Optional memo/description
+    pub memo: String,
+    /// Timestamp when request was created
+    pub created_at: i64,
+    /// Shares owed by each payer
+    pub shares: Vec<SplitShare>,
+    /// Status of the split request
+    pub status: PaymentRequestStatus,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+/// Terms supplied when creating a scheduled charge
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ScheduledChargeParams {
+    /// Amount charged per execution (in smallest units), the per-period cap for metered charges
+    pub amount: u64,
+    /// Token mint (Pubkey::default() for SOL)
+    pub token_mint: Pubkey,
+    /// The recipient of the payments
+    pub recipient: Pubkey,
+    /// Timestamp of the first execution
+    pub execute_at: i64,
+    /// Type of charge (0 one-time, 1 recurring, 2 metered)
+    pub charge_type: u8,
+    /// Billing schedule (for recurring and metered charges)
+    pub recurrence: Option<Recurrence>,
+    /// Maximum number of billed executions (None for unlimited)
+    pub max_executions: Option<u32>,
+    /// Description of the charge
+    pub memo: String,
+    /// Amount deposited into the charge vault up front (None to pull each execution from the authority)
+    pub vault_deposit: Option<u64>,
+    /// Amount paid to the executor on every billed execution (None for no tip)
+    pub keeper_tip: Option<u64>,
+    /// How missed periods are handled when a late execution lands (None for SkipMissed)
+    pub catch_up_policy: Option<CatchUpPolicy>,
+    /// Timestamp after which the charge stops executing (None for no end date)
+    pub end_at: Option<i64>,
+    /// Length of the free trial from the first execution (None for no trial)
+    pub trial_seconds: Option<u64>,
+    /// Reduced price for the first paid executions (None for no offer)
+    pub intro_offer: Option<IntroductoryOffer>,
+    /// Time after a missed payment before the charge is suspended (None for the default)
+    pub grace_period: Option<u64>,
+    /// Price per unit of reported usage (for metered charges)
+    pub unit_price: Option<u64>,
+    /// Account allowed to report usage besides the recipient (for metered charges)
+    pub meter_authority: Option<Pubkey>,
+}
+
 /// Scheduled charge account
 /// Stores information about payments that execute automatically based on time conditions
 #[account]
 pub struct ScheduledCharge {
     /// The creator/owner of the scheduled charge
     pub authority: Pubkey,
+    /// Index from the authority's profile counter, used to derive the account address
+    pub index: u64,
     /// The recipient who should receive the payment
     pub recipient: Pubkey,
-    /// Amount to be paid per execution (in smallest units)
+    /// Plan the charge subscribes to (Pubkey::default() for charges created directly)
+    pub plan: Pubkey,
+    /// Amount to be paid per execution, or the most a metered period can bill (in smallest units)
     pub amount: u64,
+    /// Tip paid to the executor on each billed execution, on top of the amount (0 for none)
+    pub keeper_tip: u64,
+    /// Executions due before this timestamp are free (None for no trial)
+    pub trial_ends_at: Option<i64>,
+    /// Introductory price and the number of paid executions still charged at it (None for no offer)
+    pub intro_offer: Option<IntroductoryOffer>,
+    /// Usage billed on each execution of a metered charge (None for fixed-price charges)
+    pub meter: Option<Meter>,
+    /// Prorated amount from a plan change added to the next execution, negative for a credit
+    pub proration_adjustment: i64,
     /// Token mint (Pubkey::default() for SOL)
     pub token_mint: Pubkey,
     /// Type of scheduled charge
     pub charge_type: ScheduledChargeType,
+    /// Where executions are paid from
+    pub funding_mode: ChargeFundingMode,
+    /// Token account executions are paid from, the authority's or the vault's (Pubkey::default() for SOL)
+    pub funding_token_account: Pubkey,
+    /// Allowance this charge holds on the authority's charge delegate (for delegated SPL token charges)
+    pub delegated_allowance: u64,
     /// Timestamp when the charge should first execute
     pub execute_at: i64,
-    /// For recurring charges: interval between executions (in seconds)
-    pub interval_seconds: Option<u64>,
+    /// For recurring charges: when the charge repeats
+    pub recurrence: Option<Recurrence>,
+    /// For recurring charges: how missed periods are handled when executed late
+    pub catch_up_policy: CatchUpPolicy,
+    /// Terms awaiting the counterparty's consent (None if no amendment is pending)
+    pub pending_amendment: Option<ChargeAmendment>,
+    /// Timestamp at which a paused charge resumes on its own (None to stay paused until resumed)
+    pub auto_resume_at: Option<i64>,
     /// Timestamp of last execution (None if never executed)
     pub last_executed_at: Option<i64>,
-    /// Maximum number of executions (None for unlimited recurring)
+    /// Maximum number of billed executions (None for unlimited recurring)
     pub max_executions: Option<u32>,
-    /// Current execution count
+    /// Timestamp after which the charge no longer executes (None for no end date)
+    pub end_at: Option<i64>,
+    /// Seconds a past-due charge keeps retrying after its due time before it is suspended
+    pub grace_period: u64,
+    /// Failed execution attempts since the last successful execution
+    pub failed_attempts: u32,
+    /// Timestamp of the last failed execution attempt (None if never failed)
+    pub last_failure_at: Option<i64>,
+    /// Billed executions so far, trial runs are not counted
     pub execution_count: u32,
     /// Optional memo/description
     pub memo: String,
@@ -79,32 +369,185 @@ pub struct ScheduledCharge {
     pub bump: u8,
 }
 
+impl Recurrence {
+    pub const LEN: usize = 1 + // variant
+        8; // largest variant (interval_seconds)
+}
+
+impl ChargeAmendment {
+    pub const LEN: usize = 32 + // proposed_by
+        8 + // amount
+        (1 + Recurrence::LEN) + // recurrence
+        (1 + 4) + // max_executions
+        32 + // recipient
+        8; // proposed_at
+}
+
+impl UserProfile {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        8 + // payment_request_count
+        8 + // split_request_count
+        8 + // scheduled_charge_count
+        8 + // plan_count
+        1; // bump
+}
+
 impl PaymentRequest {
     pub const LEN: usize = 8 + // discriminator
         32 + // authority
+        8 + // index
         32 + // recipient
         8 + // amount
+        8 + // amount_paid
+        8 + // amount_refunded
+        1 + // allow_partial
+        (1 + 32) + // designated_payer
+        (1 + 32) + // paid_by
+        (1 + 8) + // paid_at
+        (4 + MAX_REQUEST_PAYERS * PaymentContribution::LEN) + // payments
+        (1 + 8) + // escrow_timeout_seconds
+        (1 + 8) + // escrow_expires_at
         32 + // token_mint
         (4 + 200) + // memo (max 200 chars)
         8 + // created_at
+        (1 + 8) + // expires_at
         1 + // status
         1; // bump
+
+    /// Amount still to be paid before the request is settled
+    pub fn outstanding_amount(&self) -> u64 {
+        self.amount.saturating_sub(self.amount_paid)
+    }
+
+    /// Amount that can still be refunded to the payer
+    pub fn refundable_amount(&self) -> u64 {
+        self.amount_paid.saturating_sub(self.amount_refunded)
+    }
+
+    /// Check whether the request has passed its expiry deadline
+    pub fn is_expired(&self, current_time: i64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if current_time > expires_at)
+    }
+
+    /// Check whether the escrow timeout has passed
+    pub fn is_escrow_expired(&self, current_time: i64) -> bool {
+        matches!(self.escrow_expires_at, Some(escrow_expires_at) if current_time > escrow_expires_at)
+    }
+}
+
+impl PaymentContribution {
+    pub const LEN: usize = 32 + // payer
+        8 + // amount
+        8; // amount_refunded
+
+    /// Amount that can still be refunded to this payer
+    pub fn refundable_amount(&self) -> u64 {
+        self.amount.saturating_sub(self.amount_refunded)
+    }
+}
+
+impl SplitShare {
+    pub const LEN: usize = 32 + // payer
+        8 + // amount
+        (1 + 8); // paid_at
+}
+
+impl SplitRequest {
+    /// Account size for a split request with the given number of shares
+    pub fn space(share_count: usize) -> usize {
+        8 + // discriminator
+        32 + // authority
+        8 + // index
+        32 + // recipient
+        8 + // total_amount
+        8 + // amount_paid
+        32 + // token_mint
+        (4 + 200) + // memo (max 200 chars)
+        8 + // created_at
+        (4 + share_count * SplitShare::LEN) + // shares
+        1 + // status
+        1 // bump
+    }
 }
 
 impl ScheduledCharge {
     pub const LEN: usize = 8 + // discriminator
         32 + // authority
+        8 + // index
         32 + // recipient
+        32 + // plan
         8 + // amount
+        8 + // keeper_tip
+        (1 + 8) + // trial_ends_at
+        (1 + IntroductoryOffer::LEN) + // intro_offer
+        (1 + Meter::LEN) + // meter
+        8 + // proration_adjustment
         32 + // token_mint
         1 + // charge_type
+        1 + // funding_mode
+        32 + // funding_token_account
+        8 + // delegated_allowance
         8 + // execute_at
-        (1 + 8) + // interval_seconds
+        (1 + Recurrence::LEN) + // recurrence
+        (1 + 4) + // catch_up_policy
+        (1 + ChargeAmendment::LEN) + // pending_amendment
+        (1 + 8) + // auto_resume_at
         (1 + 8) + // last_executed_at
         (1 + 4) + // max_executions
+        (1 + 8) + // end_at
+        8 + // grace_period
+        4 + // failed_attempts
+        (1 + 8) + // last_failure_at
         4 + // execution_count
         (4 + 200) + // memo (max 200 chars)
         8 + // created_at
         1 + // status
         1; // bump
 }
+
+/// Subscription plan account
+/// A merchant's product definition that subscribers sign up to
+#[account]
+pub struct Plan {
+    /// The merchant offering the plan, receives every subscription payment
+    pub merchant: Pubkey,
+    /// Index from the merchant's profile counter, used to derive the account address
+    pub index: u64,
+    /// Price charged per billing cycle (in smallest units)
+    pub amount: u64,
+    /// Token mint (Pubkey::default() for SOL)
+    pub token_mint: Pubkey,
+    /// When the plan bills
+    pub recurrence: Recurrence,
+    /// Length of the free trial given to new subscribers in seconds (None for no trial)
+    pub trial_seconds: Option<u64>,
+    /// Billing cycles per subscription (None for unlimited)
+    pub max_executions: Option<u32>,
+    /// Plan description, copied to each subscription's memo
+    pub memo: String,
+    /// Number of subscriptions ever created from the plan, cancelled ones included
+    pub subscriptions_created: u64,
+    /// Whether the plan accepts new subscribers
+    pub active: bool,
+    /// Timestamp when plan was created
+    pub created_at: i64,
+    /// Bump seed for PDA derivation
+    pub bump: u8,
+}
+
+impl Plan {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // merchant
+        8 + // index
+        8 + // amount
+        32 + // token_mint
+        Recurrence::LEN + // recurrence
+        (1 + 8) + // trial_seconds
+        (1 + 4) + // max_executions
+        (4 + 200) + // memo (max 200 chars)
+        8 + // subscriptions_created
+        1 + // active
+        8 + // created_at
+        1; // bump
+}
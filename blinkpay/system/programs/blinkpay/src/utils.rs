@@ -1,8 +1,11 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, TokenAccount};
+use anchor_lang::solana_program::program_option::COption;
 use anchor_lang::system_program::{transfer, Transfer};
 
 use crate::errors::BlinkPayError;
+use crate::schedule::days_in_month;
+use crate::state::{IntroductoryOffer, Recurrence, ScheduledChargeType, SplitShareInput};
 
 /// Transfer SOL from one account to another
 /// Uses the system program's transfer instruction
@@ -52,6 +55,126 @@ pub fn transfer_spl_tokens<'info>(
     Ok(())
 }
 
+/// Transfer SOL out of a program-derived account
+/// Signs the system program's transfer instruction with the PDA seeds
+pub fn transfer_sol_signed<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // Create the accounts context
+    let accounts = Transfer {
+        from: from.clone(),
+        to: to.clone(),
+    };
+
+    // Execute the transfer
+    transfer(
+        CpiContext::new_with_signer(system_program.clone(), accounts, signer_seeds),
+        amount,
+    )?;
+
+    Ok(())
+}
+
+/// Transfer SPL tokens whose authority is a program-derived account
+/// Signs the token transfer with the PDA seeds
+pub fn transfer_spl_tokens_signed<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // Create the accounts context
+    let accounts = token::Transfer {
+        from: from.clone(),
+        to: to.clone(),
+        authority: authority.clone(),
+    };
+
+    // Execute the transfer
+    token::transfer(
+        CpiContext::new_with_signer(token_program.clone(), accounts, signer_seeds),
+        amount,
+    )?;
+
+    Ok(())
+}
+
+/// Close a token account whose authority is a program-derived account
+/// Signs with the PDA seeds and sends the account rent to the destination
+pub fn close_spl_token_account_signed<'info>(
+    token_account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // Create the accounts context
+    let accounts = token::CloseAccount {
+        account: token_account.clone(),
+        destination: destination.clone(),
+        authority: authority.clone(),
+    };
+
+    // Execute the close
+    token::close_account(
+        CpiContext::new_with_signer(token_program.clone(), accounts, signer_seeds),
+    )?;
+
+    Ok(())
+}
+
+/// Approve a delegate to transfer tokens out of an owner's token account
+/// Replaces any existing delegation on the account
+pub fn approve_spl_delegate<'info>(
+    token_account: &AccountInfo<'info>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    // Create the accounts context
+    let accounts = token::Approve {
+        to: token_account.clone(),
+        delegate: delegate.clone(),
+        authority: owner.clone(),
+    };
+
+    // Execute the approval
+    token::approve(
+        CpiContext::new(token_program.clone(), accounts),
+        amount,
+    )?;
+
+    Ok(())
+}
+
+/// Return the allowance a token account currently grants to a delegate
+/// Zero when the account is delegated to someone else or not at all
+pub fn delegated_allowance<'info>(
+    token_account: &AccountInfo<'info>,
+    delegate: &Pubkey,
+) -> Result<u64> {
+    let token_account_data = TokenAccount::try_deserialize(&mut &token_account.data.borrow()[..])?;
+
+    Ok(match token_account_data.delegate {
+        COption::Some(current) if current == *delegate => token_account_data.delegated_amount,
+        _ => 0,
+    })
+}
+
+/// Return the token balance held by a token account
+pub fn token_account_balance<'info>(token_account: &AccountInfo<'info>) -> Result<u64> {
+    let token_account_data = TokenAccount::try_deserialize(&mut &token_account.data.borrow()[..])?;
+
+    Ok(token_account_data.amount)
+}
+
 /// Validate that a token account is owned by the expected owner
 pub fn validate_token_account_ownership<'info>(
     token_account: &AccountInfo<'info>,
@@ -66,6 +189,20 @@ pub fn validate_token_account_ownership<'info>(
     Ok(())
 }
 
+/// Validate that a token account holds the expected mint
+pub fn validate_token_account_mint<'info>(
+    token_account: &AccountInfo<'info>,
+    expected_mint: &Pubkey,
+) -> Result<()> {
+    let token_account_data = TokenAccount::try_deserialize(&mut &token_account.data.borrow()[..])?;
+
+    if token_account_data.mint != *expected_mint {
+        return err!(BlinkPayError::InvalidTokenMint);
+    }
+
+    Ok(())
+}
+
 /// Check if a pubkey is the default pubkey (used to identify SOL payments)
 pub fn is_sol_token(mint: &Pubkey) -> bool {
     *mint == Pubkey::default()
@@ -109,6 +246,11 @@ pub const MAX_EXECUTIONS: u32 = 1000; // Maximum executions for recurring charge
 pub const MIN_INTERVAL_SECONDS: u64 = 3600; // 1 hour minimum interval
 pub const MAX_INTERVAL_SECONDS: u64 = 31536000; // 1 year maximum interval
 pub const TIME_BUFFER_SECONDS: i64 = 300; // 5 minutes buffer for time validation
+pub const MAX_SPLIT_PAYERS: usize = 10; // Maximum payers on a split request
+pub const MAX_REQUEST_PAYERS: usize = 10; // Maximum distinct payers on a payment request
+pub const DEFAULT_GRACE_PERIOD_SECONDS: u64 = 604800; // 7 days before a past-due charge is suspended
+pub const DUNNING_RETRY_BASE_SECONDS: i64 = 3600; // 1 hour before the first retry
+pub const DUNNING_MAX_RETRY_SECONDS: i64 = 86400; // Retries back off to at most once a day
 
 /// Enhanced amount validation with security bounds
 pub fn validate_amount(amount: u64) -> Result<()> {
@@ -153,20 +295,40 @@ pub fn validate_future_timestamp(timestamp: i64, current_time: i64) -> Result<()
     Ok(())
 }
 
+/// Validate an expiry deadline is in the future
+pub fn validate_expiry(expires_at: i64, current_time: i64) -> Result<()> {
+    if expires_at <= current_time {
+        return err!(BlinkPayError::InvalidTimestamp);
+    }
+
+    Ok(())
+}
+
 /// Comprehensive input validation for scheduled charges
 pub fn validate_scheduled_charge_params(
     amount: u64,
     execute_at: i64,
     max_executions: Option<u32>,
-    interval_seconds: Option<u64>,
+    charge_type: &ScheduledChargeType,
+    recurrence: Option<&Recurrence>,
     current_time: i64,
 ) -> Result<()> {
-    // Amount validation
-    validate_amount(amount)?;
-
     // Time validation
     validate_future_timestamp(execute_at, current_time)?;
 
+    validate_charge_terms(amount, max_executions, charge_type, recurrence)
+}
+
+/// Validate the terms of a scheduled charge, on creation or amendment
+pub fn validate_charge_terms(
+    amount: u64,
+    max_executions: Option<u32>,
+    charge_type: &ScheduledChargeType,
+    recurrence: Option<&Recurrence>,
+) -> Result<()> {
+    // Amount validation
+    validate_amount(amount)?;
+
     // Max executions validation
     if let Some(max_exec) = max_executions {
         if max_exec == 0 || max_exec > MAX_EXECUTIONS {
@@ -174,14 +336,192 @@ pub fn validate_scheduled_charge_params(
         }
     }
 
-    // Interval validation for recurring charges
-    if let Some(interval) = interval_seconds {
-        validate_interval(interval)?;
+    // Recurring and metered charges need a recurrence, one-time charges must not have one
+    match (charge_type, recurrence) {
+        (ScheduledChargeType::Recurring | ScheduledChargeType::Metered, Some(recurrence)) => {
+            validate_recurrence(recurrence)?
+        }
+        (ScheduledChargeType::OneTime, None) => {}
+        _ => return err!(BlinkPayError::InvalidRecurrence),
+    }
+
+    Ok(())
+}
+
+/// Validate the trial length and introductory offer of a scheduled charge
+pub fn validate_introductory_terms(
+    amount: u64,
+    charge_type: &ScheduledChargeType,
+    trial_seconds: Option<u64>,
+    intro_offer: Option<&IntroductoryOffer>,
+) -> Result<()> {
+    if trial_seconds.is_none() && intro_offer.is_none() {
+        return Ok(());
+    }
+
+    // Only recurring charges have later executions to switch to the regular amount
+    if *charge_type != ScheduledChargeType::Recurring {
+        return err!(BlinkPayError::InvalidIntroductoryTerms);
+    }
+
+    if let Some(trial_seconds) = trial_seconds {
+        if trial_seconds == 0 || trial_seconds > MAX_INTERVAL_SECONDS {
+            return err!(BlinkPayError::InvalidIntroductoryTerms);
+        }
+    }
+
+    if let Some(offer) = intro_offer {
+        if offer.amount == 0 || offer.amount >= amount || offer.executions == 0 || offer.executions > MAX_EXECUTIONS {
+            return err!(BlinkPayError::InvalidIntroductoryTerms);
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the meter terms of a scheduled charge
+/// Metered charges need a unit price, fixed-price charges must not set meter terms
+pub fn validate_meter_terms(
+    charge_type: &ScheduledChargeType,
+    unit_price: Option<u64>,
+    meter_authority: Option<&Pubkey>,
+) -> Result<()> {
+    match (charge_type, unit_price) {
+        (ScheduledChargeType::Metered, Some(unit_price)) => validate_amount(unit_price)?,
+        (ScheduledChargeType::Metered, None) => return err!(BlinkPayError::InvalidMeter),
+        (_, Some(_)) => return err!(BlinkPayError::InvalidMeter),
+        (_, None) if meter_authority.is_some() => return err!(BlinkPayError::InvalidMeter),
+        (_, None) => {}
+    }
+
+    Ok(())
+}
+
+/// Earliest time a past-due charge may be retried
+/// The wait doubles with each failed attempt, up to a day
+pub fn dunning_retry_at(last_failure_at: i64, failed_attempts: u32) -> i64 {
+    // Five doublings of the base already exceed the cap
+    let doublings = failed_attempts.saturating_sub(1).min(5);
+    let backoff = (DUNNING_RETRY_BASE_SECONDS << doublings).min(DUNNING_MAX_RETRY_SECONDS);
+
+    last_failure_at.saturating_add(backoff)
+}
+
+/// Prorated price difference for the unused part of a billing period
+/// Positive when the new price is higher, negative for a credit
+pub fn prorate_price_change(old_price: u64, new_price: u64, remaining: i64, period: i64) -> i64 {
+    if remaining <= 0 || period <= 0 {
+        return 0;
+    }
+
+    let difference = new_price as i128 - old_price as i128;
+    let prorated = difference * remaining.min(period) as i128 / period as i128;
+
+    prorated.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Apply a carried proration adjustment to the amount of an execution
+/// Returns the amount to bill and any credit left over for later executions
+pub fn apply_proration(amount: u64, adjustment: i64) -> (u64, i64) {
+    if adjustment >= 0 {
+        return (amount.saturating_add(adjustment as u64), 0);
+    }
+
+    let credit = adjustment.unsigned_abs();
+    if credit >= amount {
+        (0, -((credit - amount) as i64))
+    } else {
+        (amount - credit, 0)
+    }
+}
+
+/// Total amount a scheduled charge can still pull over its remaining executions
+/// Unlimited recurring charges are bounded by the global execution cap
+pub fn scheduled_charge_allowance(
+    amount: u64,
+    charge_type: &ScheduledChargeType,
+    max_executions: Option<u32>,
+    execution_count: u32,
+) -> u64 {
+    let executions = match charge_type {
+        ScheduledChargeType::OneTime => 1,
+        ScheduledChargeType::Recurring | ScheduledChargeType::Metered => {
+            max_executions.unwrap_or(MAX_EXECUTIONS)
+        }
+    };
+
+    amount.saturating_mul(executions.saturating_sub(execution_count) as u64)
+}
+
+/// Validate a recurrence rule
+pub fn validate_recurrence(recurrence: &Recurrence) -> Result<()> {
+    let valid = match *recurrence {
+        Recurrence::EveryNSeconds { interval_seconds } => {
+            validate_interval(interval_seconds)?;
+            true
+        }
+        Recurrence::Weekly { weekday } => weekday < 7,
+        Recurrence::Monthly { day_of_month } => (1..=31).contains(&day_of_month),
+        // Allow February 29 by checking against a leap year
+        Recurrence::Yearly { month, day } => {
+            (1..=12).contains(&month) && day >= 1 && day <= days_in_month(2000, month)
+        }
+    };
+
+    if !valid {
+        return err!(BlinkPayError::InvalidRecurrence);
+    }
+
+    Ok(())
+}
+
+/// Validate escrow terms for a payment request
+pub fn validate_escrow_terms(
+    amount: u64,
+    token_mint: &Pubkey,
+    allow_partial: bool,
+    timeout_seconds: u64,
+) -> Result<()> {
+    // Escrow is released or refunded in one piece
+    if allow_partial {
+        return err!(BlinkPayError::InvalidEscrowTerms);
+    }
+
+    if !(MIN_INTERVAL_SECONDS..=MAX_INTERVAL_SECONDS).contains(&timeout_seconds) {
+        return err!(BlinkPayError::InvalidEscrowTerms);
+    }
+
+    // An empty SOL vault must receive at least the rent-exempt minimum
+    if is_sol_token(token_mint) && amount < Rent::get()?.minimum_balance(0) {
+        return err!(BlinkPayError::InvalidAmount);
     }
 
     Ok(())
 }
 
+/// Validate split shares and return the total amount owed
+pub fn validate_split_shares(shares: &[SplitShareInput]) -> Result<u64> {
+    if shares.is_empty() || shares.len() > MAX_SPLIT_PAYERS {
+        return err!(BlinkPayError::InvalidSplitShares);
+    }
+
+    let mut total: u64 = 0;
+    for (index, share) in shares.iter().enumerate() {
+        validate_amount(share.amount)?;
+
+        // Each payer may only appear once
+        if shares[..index].iter().any(|other| other.payer == share.payer) {
+            return err!(BlinkPayError::InvalidSplitShares);
+        }
+
+        total = safe_add(total, share.amount)?;
+    }
+
+    validate_amount(total)?;
+
+    Ok(total)
+}
+
 /// Validate recipient is not the same as authority (prevent self-transfers)
 pub fn validate_recipient_not_authority(recipient: &Pubkey, authority: &Pubkey) -> Result<()> {
     if recipient == authority {
@@ -50,24 +50,40 @@ async function testIntegration() {
     console.log('\n📝 Test 1: Creating payment request...')
     const recipient = Keypair.generate().publicKey
     const amount = new anchor.BN(1000000) // 0.001 SOL
-    const tokenMint = new PublicKey('11111111111111111111111111111112') // SOL
+    const tokenMint = anchor.web3.SystemProgram.programId // SOL
     const memo = 'Integration test payment'
-    const currentTime = new anchor.BN(Math.floor(Date.now() / 1000))
+
+    // Accounts are seeded by the authority's profile counter, so create the profile first
+    const [userProfile] = PublicKey.findProgramAddressSync(
+      [Buffer.from('user_profile'), testWallet.publicKey.toBuffer()],
+      PROGRAM_ID
+    )
+    await program.methods
+      .initializeUserProfile()
+      .accounts({
+        authority: testWallet.publicKey,
+        userProfile,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([testWallet])
+      .rpc()
+
+    const profile = await program.account.userProfile.fetch(userProfile)
+    const [paymentRequest] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('payment_request'),
+        testWallet.publicKey.toBuffer(),
+        profile.paymentRequestCount.toArrayLike(Buffer, 'le', 8),
+      ],
+      PROGRAM_ID
+    )
 
     const tx1 = await program.methods
-      .createPaymentRequest(amount, tokenMint, recipient, memo, currentTime)
+      .createPaymentRequest(amount, tokenMint, recipient, memo, null, false, null, null)
       .accounts({
         authority: testWallet.publicKey,
-        paymentRequest: PublicKey.findProgramAddressSync(
-          [
-            Buffer.from('payment_request'),
-            testWallet.publicKey.toBuffer(),
-            recipient.toBuffer(),
-            amount.toArrayLike(Buffer, 'le', 8),
-            currentTime.toArrayLike(Buffer, 'le', 8),
-          ],
-          PROGRAM_ID
-        )[0],
+        userProfile,
+        paymentRequest,
         systemProgram: anchor.web3.SystemProgram.programId,
         clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
       })